@@ -0,0 +1,161 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Allowlists: comments that have been reviewed and accepted, to leave out of
+//! reports
+//!
+//! An allowlist file has one entry per line.  Each entry is either a location,
+//! "FILE:LINE", matching the comment in that file that includes that line, or
+//! the content hash of a comment (see [`todos::Comment::content_hash()`]),
+//! matching comments with that text wherever they are:
+//!
+//! ```text
+//! # reviewed in the March cleanup
+//! src/lib.rs:42
+//! 8c2f0b9e41d7a365  src/main.rs:120: TODO: handle wide terminals
+//! ```
+//!
+//! Anything after the first word of a line is only there for people reading
+//! the file, as are blank lines and lines starting with "#".  A FILE matches
+//! a comment's path if it's the same or the end of it (so "src/lib.rs"
+//! matches "./src/lib.rs" and "crate/src/lib.rs").  Hashes don't change when
+//! a comment is moved or reformatted, so they're what [`serialize()`] writes.
+
+use anyhow::anyhow;
+use anyhow::Context;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+use todos::Comment;
+
+/// One entry in an allowlist
+#[derive(Debug)]
+enum Entry {
+    /// a comment including this line of this file
+    Location(PathBuf, usize),
+    /// comments with this content hash
+    Hash(String),
+}
+
+/// The entries of an allowlist file, with whether each has matched a comment
+#[derive(Debug, Default)]
+pub struct Allowlist {
+    /// each entry, the line of the file it's on, and whether it has matched
+    entries: Vec<(Entry, usize, bool)>,
+}
+
+impl Allowlist {
+    /// Loads the allowlist file at `path`
+    pub fn load(path: &Path) -> Result<Allowlist, anyhow::Error> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("read {:?}", path.display()))?;
+        Allowlist::parse(&contents)
+            .with_context(|| format!("parse {:?}", path.display()))
+    }
+
+    /// Parses the contents of an allowlist file
+    pub fn parse(contents: &str) -> Result<Allowlist, anyhow::Error> {
+        let mut entries = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            let Some(word) = line.split_whitespace().next() else {
+                continue;
+            };
+            if word.starts_with('#') {
+                continue;
+            }
+
+            let entry = if let Some((file, line)) = word.rsplit_once(':') {
+                let line =
+                    line.parse().ok().filter(|&n| n > 0).ok_or_else(|| {
+                        anyhow!("line {}: bad line number {:?}", i + 1, line)
+                    })?;
+                let file = file.strip_prefix("./").unwrap_or(file);
+                Entry::Location(PathBuf::from(file), line)
+            } else if word.len() == 16
+                && word.bytes().all(|b| b.is_ascii_hexdigit())
+            {
+                Entry::Hash(word.to_ascii_lowercase())
+            } else {
+                return Err(anyhow!(
+                    "line {}: expected \"FILE:LINE\" or a content hash, \
+                     found {:?}",
+                    i + 1,
+                    word
+                ));
+            };
+            entries.push((entry, i + 1, false));
+        }
+        Ok(Allowlist { entries })
+    }
+
+    /// Returns whether `c` matches any entry, noting which entries it matches
+    pub fn allows(&mut self, c: &Comment) -> bool {
+        let mut hash = None;
+        let mut allowed = false;
+        for (entry, _, matched) in &mut self.entries {
+            let matches = match entry {
+                Entry::Location(file, line) => {
+                    c.line <= *line
+                        && *line <= c.end_line
+                        && Path::new(&c.file).ends_with(file)
+                }
+                Entry::Hash(h) => {
+                    *h == *hash.get_or_insert_with(|| c.content_hash())
+                }
+            };
+            if matches {
+                *matched = true;
+                allowed = true;
+            }
+        }
+        allowed
+    }
+
+    /// Returns each entry that hasn't matched any comment (e.g., because the
+    /// comment was removed), as the line of the file it's on and how it was
+    /// written there
+    pub fn unmatched(&self) -> Vec<(usize, String)> {
+        self.entries
+            .iter()
+            .filter(|(_, _, matched)| !matched)
+            .map(|(entry, line, _)| {
+                let text = match entry {
+                    Entry::Location(file, n) => {
+                        format!("{}:{}", file.display(), n)
+                    }
+                    Entry::Hash(h) => h.clone(),
+                };
+                (*line, text)
+            })
+            .collect()
+    }
+}
+
+/// Returns the contents of an allowlist file with an entry for each of
+/// `comments`, by content hash
+///
+/// Each entry is followed by where the comment was found and its first line,
+/// for people reading the file.  Comments with the same hash (including a
+/// comment with several labels) share one entry.
+pub fn serialize<'a>(
+    comments: impl IntoIterator<Item = &'a Comment>,
+) -> String {
+    let mut comments: Vec<&Comment> = comments.into_iter().collect();
+    comments.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    let mut seen = BTreeSet::new();
+    let mut out = String::new();
+    for c in comments {
+        let hash = c.content_hash();
+        if seen.insert(hash.clone()) {
+            out.push_str(&format!(
+                "{}  {}:{}: {}\n",
+                hash,
+                c.file,
+                c.line,
+                c.first_line()
+            ));
+        }
+    }
+    out
+}
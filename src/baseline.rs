@@ -0,0 +1,121 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Baselines: comments that already existed, to leave out of later reports
+//!
+//! A baseline file is a JSON object listing comments by file and stable ID
+//! (see [`todos::stable_id()`]):
+//!
+//! ```text
+//! {"version":1,"comments":[
+//! {"file":"src/lib.rs","line":42,"label":"TODO","id":"8c2f..."}
+//! ]}
+//! ```
+//!
+//! As in [`crate::diff`], comments are matched by ID rather than by line, so
+//! a comment that merely moved still matches.  The line and label are only
+//! there for people reading the file.  A comment whose file isn't in the
+//! baseline also matches an entry with the same ID for a file that no longer
+//! exists, so that renaming a file doesn't make its comments new.
+
+use crate::json;
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::path::Path;
+use todos::Comment;
+
+/// The comments in a baseline that haven't been matched yet
+#[derive(Debug, Default)]
+pub struct Baseline {
+    /// how many times each (file, ID) appears
+    entries: BTreeMap<(String, String), usize>,
+}
+
+impl Baseline {
+    /// Loads the baseline file at `path`
+    pub fn load(path: &Path) -> Result<Baseline, anyhow::Error> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("read {:?}", path.display()))?;
+        Baseline::parse(&contents)
+            .with_context(|| format!("parse {:?}", path.display()))
+    }
+
+    /// Parses the contents of a baseline file
+    pub fn parse(contents: &str) -> Result<Baseline, anyhow::Error> {
+        let value = json::parse(contents)?;
+        match value.get("version") {
+            Some(json::Value::Number(n)) if *n == 1.0 => (),
+            _ => bail!("expected \"version\": 1"),
+        }
+        let Some(json::Value::Array(comments)) = value.get("comments") else {
+            bail!("expected a \"comments\" array");
+        };
+
+        let mut entries: BTreeMap<(String, String), usize> = BTreeMap::new();
+        for (i, c) in comments.iter().enumerate() {
+            let field = |name: &str| {
+                c.get(name).and_then(json::Value::as_str).ok_or_else(|| {
+                    anyhow!("comment {}: expected a string {:?}", i + 1, name)
+                })
+            };
+            let key = (field("file")?.to_string(), field("id")?.to_string());
+            *entries.entry(key).or_default() += 1;
+        }
+        Ok(Baseline { entries })
+    }
+
+    /// Returns whether `c` is in the baseline, removing the entry that it
+    /// matches so that each entry only matches one comment
+    pub fn take(&mut self, c: &Comment) -> bool {
+        let exact = (c.file.clone(), c.id.clone());
+        let key = if self.entries.contains_key(&exact) {
+            Some(exact)
+        } else {
+            self.entries
+                .keys()
+                .find(|(file, id)| *id == c.id && !Path::new(file).exists())
+                .cloned()
+        };
+        let Some(key) = key else {
+            return false;
+        };
+
+        let count = self.entries.get_mut(&key).expect("key was found");
+        *count -= 1;
+        if *count == 0 {
+            self.entries.remove(&key);
+        }
+        true
+    }
+
+    /// Returns how many entries haven't matched any comment (i.e., comments
+    /// that have been removed since the baseline was written)
+    pub fn remaining(&self) -> usize {
+        self.entries.values().sum()
+    }
+}
+
+/// Returns the contents of a baseline file listing `comments`
+pub fn serialize<'a>(
+    comments: impl IntoIterator<Item = &'a Comment>,
+) -> String {
+    let mut out = String::from("{\"version\":1,\"comments\":[\n");
+    let lines: Vec<String> = comments
+        .into_iter()
+        .map(|c| {
+            format!(
+                "{{\"file\":{},\"line\":{},\"label\":{},\"id\":{}}}",
+                json::quote(&c.file),
+                c.line,
+                json::quote(&c.label),
+                json::quote(&c.id)
+            )
+        })
+        .collect();
+    out.push_str(&lines.join(",\n"));
+    out.push_str("\n]}\n");
+    out
+}
@@ -0,0 +1,347 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reading defaults from a `.todos.toml` file
+//!
+//! The file has a few top-level keys, all optional:
+//!
+//! ```text
+//! keywords = ["TODO", "FIXME", "HACK"]   # instead of the default keywords
+//! skip = ["node_modules", "vendor"]      # like --skip
+//! format = "json"                        # like --format
+//! ignore-case = true                     # like --ignore-case
+//! ```
+//!
+//! Options given on the command line take precedence over the file, which
+//! takes precedence over the built-in defaults.  We only need a small part of
+//! TOML for this -- strings, booleans, and arrays of them, without tables --
+//! so rather than pull in a TOML library, we parse that part here.
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// name of the file we look for
+pub const FILE_NAME: &str = ".todos.toml";
+
+/// The defaults set by a `.todos.toml` file
+#[derive(Debug, Default)]
+pub struct ConfigFile {
+    /// keywords to look for instead of the default ones
+    pub keywords: Option<Vec<String>>,
+    /// names of directories to skip wherever they are in the tree
+    pub skip: Option<Vec<String>>,
+    /// name of the report format
+    pub format: Option<String>,
+    /// whether to match keywords regardless of case
+    pub ignore_case: Option<bool>,
+}
+
+impl ConfigFile {
+    /// Loads the config file at `path`
+    pub fn load(path: &Path) -> Result<ConfigFile, anyhow::Error> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("read {:?}", path.display()))?;
+        ConfigFile::parse(&contents)
+            .with_context(|| format!("parse {:?}", path.display()))
+    }
+
+    /// Loads the config file in the first of `dirs` that has one, if any
+    /// does, returning its path too
+    pub fn find<'a>(
+        dirs: impl IntoIterator<Item = &'a Path>,
+    ) -> Result<Option<(PathBuf, ConfigFile)>, anyhow::Error> {
+        for dir in dirs {
+            let path = dir.join(FILE_NAME);
+            if path.is_file() {
+                let config = ConfigFile::load(&path)?;
+                return Ok(Some((path, config)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Parses the contents of a config file
+    pub fn parse(contents: &str) -> Result<ConfigFile, anyhow::Error> {
+        let mut config = ConfigFile::default();
+        for (key, (line, value)) in parse_toml(contents)? {
+            let error = |expected: &str| {
+                anyhow!("line {}: expected {} for {:?}", line, expected, key)
+            };
+            match key.as_str() {
+                "keywords" | "skip" => {
+                    let Value::Array(items) = value else {
+                        return Err(error("an array of strings"));
+                    };
+                    let strings = items
+                        .into_iter()
+                        .map(|item| match item {
+                            Value::String(s) if !s.trim().is_empty() => {
+                                Ok(s.trim().to_string())
+                            }
+                            _ => Err(error("an array of non-empty strings")),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if key == "keywords" {
+                        config.keywords = Some(strings);
+                    } else {
+                        config.skip = Some(strings);
+                    }
+                }
+                "format" => {
+                    let Value::String(format) = value else {
+                        return Err(error("a string"));
+                    };
+                    config.format = Some(format);
+                }
+                "ignore-case" => {
+                    let Value::Bool(ignore_case) = value else {
+                        return Err(error("true or false"));
+                    };
+                    config.ignore_case = Some(ignore_case);
+                }
+                _ => bail!(
+                    "line {}: unknown key {:?} (expected keywords, skip, \
+                     format, or ignore-case)",
+                    line,
+                    key
+                ),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// A value in the part of TOML we understand
+#[derive(Debug)]
+enum Value {
+    String(String),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+/// Parses `input` as a TOML document with only top-level keys, returning each
+/// key's value and the line it's on
+fn parse_toml(
+    input: &str,
+) -> Result<BTreeMap<String, (usize, Value)>, anyhow::Error> {
+    let mut parser = Parser { input: input.as_bytes(), pos: 0, line: 1 };
+    let mut values = BTreeMap::new();
+    loop {
+        parser.skip_blank_lines();
+        match parser.peek() {
+            None => return Ok(values),
+            Some(b'[') => {
+                return Err(parser.error("tables are not supported here"))
+            }
+            Some(_) => (),
+        }
+
+        let line = parser.line;
+        let key = parser.parse_key()?;
+        parser.skip_spaces();
+        if parser.peek() != Some(b'=') {
+            return Err(parser.error("expected \"=\" after key"));
+        }
+        parser.pos += 1;
+        parser.skip_spaces();
+        let value = parser.parse_value()?;
+        parser.skip_spaces();
+        parser.skip_comment();
+        let rest = &parser.input[parser.pos..];
+        if !(rest.is_empty()
+            || rest.starts_with(b"\n")
+            || rest.starts_with(b"\r\n"))
+        {
+            return Err(parser.error("expected the end of the line"));
+        }
+        if values.insert(key.clone(), (line, value)).is_some() {
+            bail!("line {}: {:?} is given more than once", line, key);
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    /// line number of `pos`, counting from 1
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!("line {}: {}", self.line, message)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    /// Skips spaces and tabs (but not newlines)
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skips a comment up to (but not including) the end of the line
+    fn skip_comment(&mut self) {
+        if self.peek() == Some(b'#') {
+            while self.peek().is_some_and(|b| b != b'\n') {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Skips whitespace, newlines, and comments
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some(b'\n') => self.line += 1,
+                Some(b'\r') => (),
+                _ => return,
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Parses a bare or quoted key
+    fn parse_key(&mut self) -> Result<String, anyhow::Error> {
+        match self.peek() {
+            Some(b'"') => return self.parse_basic_string(),
+            Some(b'\'') => return self.parse_literal_string(),
+            _ => (),
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|b| {
+            b.is_ascii_alphanumeric() || b == b'_' || b == b'-'
+        }) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error("expected a key"));
+        }
+        Ok(String::from_utf8_lossy(&self.input[start..self.pos]).into_owned())
+    }
+
+    fn parse_value(&mut self) -> Result<Value, anyhow::Error> {
+        let rest = &self.input[self.pos..];
+        match self.peek() {
+            Some(b'"') => self.parse_basic_string().map(Value::String),
+            Some(b'\'') => self.parse_literal_string().map(Value::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_blank_lines();
+                    if self.peek() == Some(b']') {
+                        self.pos += 1;
+                        return Ok(Value::Array(items));
+                    }
+                    items.push(self.parse_value()?);
+                    self.skip_blank_lines();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => (),
+                        _ => return Err(self.error("expected \",\" or \"]\"")),
+                    }
+                }
+            }
+            _ if rest.starts_with(b"true") => {
+                self.pos += 4;
+                Ok(Value::Bool(true))
+            }
+            _ if rest.starts_with(b"false") => {
+                self.pos += 5;
+                Ok(Value::Bool(false))
+            }
+            _ => Err(self.error("expected a string, boolean, or array")),
+        }
+    }
+
+    /// Parses a string in double quotes, starting at its opening quote
+    fn parse_basic_string(&mut self) -> Result<String, anyhow::Error> {
+        if self.input[self.pos..].starts_with(b"\"\"\"") {
+            return Err(self.error("multi-line strings are not supported here"));
+        }
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let Some(b) = self.peek() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\n' => return Err(self.error("unterminated string")),
+                b'\\' => {
+                    let Some(escape) = self.peek() else {
+                        return Err(self.error("unterminated string"));
+                    };
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.parse_unicode_escape(4)?,
+                        b'U' => self.parse_unicode_escape(8)?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                b => bytes.push(b),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    /// Parses the `digits` hex digits of a `\u` or `\U` escape
+    fn parse_unicode_escape(
+        &mut self,
+        digits: usize,
+    ) -> Result<char, anyhow::Error> {
+        let hex = self
+            .input
+            .get(self.pos..self.pos + digits)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        let c = u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += digits;
+        Ok(c)
+    }
+
+    /// Parses a string in single quotes (which has no escapes), starting at
+    /// its opening quote
+    fn parse_literal_string(&mut self) -> Result<String, anyhow::Error> {
+        if self.input[self.pos..].starts_with(b"'''") {
+            return Err(self.error("multi-line strings are not supported here"));
+        }
+        self.pos += 1;
+        let start = self.pos;
+        while self.peek().is_some_and(|b| b != b'\'' && b != b'\n') {
+            self.pos += 1;
+        }
+        if self.peek() != Some(b'\'') {
+            return Err(self.error("unterminated string"));
+        }
+        let s = std::str::from_utf8(&self.input[start..self.pos])
+            .map_err(|_| self.error("invalid UTF-8"))?
+            .to_string();
+        self.pos += 1;
+        Ok(s)
+    }
+}
@@ -0,0 +1,49 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Comparing two sets of comments
+//!
+//! Comments are matched up by file and stable ID (see [`todos::stable_id()`]),
+//! ignoring line numbers, so that a comment that merely moved (because code
+//! above it changed) is not reported as removed and re-added.
+
+use std::collections::BTreeMap;
+use todos::Comment;
+
+/// The differences between two sets of comments
+#[derive(Debug, Default)]
+pub struct Diff {
+    /// comments in the new set that aren't in the old one
+    pub added: Vec<Comment>,
+    /// comments in the old set that aren't in the new one
+    pub removed: Vec<Comment>,
+}
+
+/// Returns the comments `added` and `removed` going from `old` to `new`
+///
+/// When identical comments appear several times in one file, only the
+/// difference in how many there are is reported.
+pub fn diff(old: Vec<Comment>, new: Vec<Comment>) -> Diff {
+    let mut old_by_key: BTreeMap<_, Vec<Comment>> = BTreeMap::new();
+    for c in old {
+        old_by_key.entry((c.file.clone(), c.id.clone())).or_default().push(c);
+    }
+
+    let mut diff = Diff::default();
+    for c in new {
+        let key = (c.file.clone(), c.id.clone());
+        match old_by_key.get_mut(&key).and_then(|matches| matches.pop()) {
+            Some(_) => (),
+            None => diff.added.push(c),
+        }
+    }
+    diff.removed = old_by_key.into_values().flatten().collect();
+
+    let order = |a: &Comment, b: &Comment| {
+        (&a.label, &a.file, a.line).cmp(&(&b.label, &b.file, b.line))
+    };
+    diff.added.sort_by(order);
+    diff.removed.sort_by(order);
+    diff
+}
@@ -0,0 +1,331 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Helpers for getting information out of git
+//!
+//! We shell out to `git(1)` rather than linking a git implementation.
+
+use anyhow::bail;
+use anyhow::Context;
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+
+/// Runs `git` with arguments `args` in directory `dir`, returning its standard
+/// output
+pub fn git(dir: &Path, args: &[&str]) -> Result<String, anyhow::Error> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("running git")?;
+    if !output.status.success() {
+        bail!(
+            "git {}: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    String::from_utf8(output.stdout).with_context(|| {
+        format!("git {}: output was not UTF-8", args.join(" "))
+    })
+}
+
+/// Returns the path and contents of each file in the tree of commit (or other
+/// tree-ish) `rev` for which `want(path)` returns true
+///
+/// Paths are relative to the top of the repository and use "/" as the
+/// separator.  Symlinks and submodules are skipped.
+pub fn read_tree<F>(
+    dir: &Path,
+    rev: &str,
+    mut want: F,
+) -> Result<Vec<(String, Vec<u8>)>, anyhow::Error>
+where
+    F: FnMut(&str) -> bool,
+{
+    // Each entry looks like "MODE TYPE OBJECT\tPATH", terminated by NUL.
+    let listing = git(dir, &["ls-tree", "-r", "-z", "--full-tree", rev, "--"])?;
+    let mut paths = Vec::new();
+    let mut objects = String::new();
+    for entry in listing.split('\0').filter(|e| !e.is_empty()) {
+        let (info, path) = entry
+            .split_once('\t')
+            .with_context(|| format!("git ls-tree: bad entry {:?}", entry))?;
+        let fields: Vec<&str> = info.split(' ').collect();
+        let [mode, kind, object] = fields[..] else {
+            bail!("git ls-tree: bad entry {:?}", entry);
+        };
+        if kind != "blob" || mode == "120000" || !want(path) {
+            continue;
+        }
+        paths.push(path.to_string());
+        objects.push_str(object);
+        objects.push('\n');
+    }
+
+    let contents = cat_blobs(dir, &objects)?;
+    if contents.len() != paths.len() {
+        bail!(
+            "git cat-file: expected {} objects, got {}",
+            paths.len(),
+            contents.len()
+        );
+    }
+    Ok(paths.into_iter().zip(contents).collect())
+}
+
+/// A file with staged changes
+pub struct StagedFile {
+    /// path relative to the top of the repository, using "/" as the separator
+    pub path: String,
+    /// first and last line of each run of lines that the changes touch
+    pub changed: Vec<(usize, usize)>,
+    /// staged contents
+    pub contents: Vec<u8>,
+}
+
+/// Returns each file with staged changes (other than deletions) for which
+/// `want(path)` returns true
+///
+/// Paths are relative to the top of the repository containing `dir` and use
+/// "/" as the separator.  A renamed file counts as entirely new.
+pub fn read_staged<F>(
+    dir: &Path,
+    mut want: F,
+) -> Result<Vec<StagedFile>, anyhow::Error>
+where
+    F: FnMut(&str) -> bool,
+{
+    let top = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let top = Path::new(top.trim_end_matches('\n'));
+    let names = git(
+        top,
+        &[
+            "diff",
+            "--cached",
+            "--name-only",
+            "-z",
+            "--no-renames",
+            "--diff-filter=ACMR",
+        ],
+    )?;
+
+    let mut files = Vec::new();
+    let mut objects = String::new();
+    for path in names.split('\0').filter(|p| !p.is_empty()) {
+        // cat-file reads one name per line, so it can't name these.
+        if path.contains('\n') || !want(path) {
+            continue;
+        }
+        let patch = git(
+            top,
+            &[
+                "--literal-pathspecs",
+                "diff",
+                "--cached",
+                "-U0",
+                "--no-color",
+                "--no-ext-diff",
+                "--no-renames",
+                "--",
+                path,
+            ],
+        )?;
+        files.push((path.to_string(), hunk_ranges(&patch)?));
+        objects.push(':');
+        objects.push_str(path);
+        objects.push('\n');
+    }
+
+    let contents = cat_blobs(top, &objects)?;
+    if contents.len() != files.len() {
+        bail!(
+            "git cat-file: expected {} objects, got {}",
+            files.len(),
+            contents.len()
+        );
+    }
+    Ok(files
+        .into_iter()
+        .zip(contents)
+        .map(|((path, changed), contents)| StagedFile {
+            path,
+            changed,
+            contents,
+        })
+        .collect())
+}
+
+/// Returns the first and last line on the new side of each hunk in `patch`
+/// (produced with `-U0`), skipping hunks that only remove lines
+fn hunk_ranges(patch: &str) -> Result<Vec<(usize, usize)>, anyhow::Error> {
+    // Each hunk header looks like "@@ -START[,COUNT] +START[,COUNT] @@".
+    let mut ranges = Vec::new();
+    for header in patch.lines().filter_map(|l| l.strip_prefix("@@ ")) {
+        let bad = || format!("git diff: bad hunk header {:?}", header);
+        let new_side = header
+            .split(' ')
+            .find_map(|field| field.strip_prefix('+'))
+            .with_context(bad)?;
+        let (start, count) =
+            new_side.split_once(',').unwrap_or((new_side, "1"));
+        let start: usize = start.parse().ok().with_context(bad)?;
+        let count: usize = count.parse().ok().with_context(bad)?;
+        if count > 0 {
+            ranges.push((start, start + count - 1));
+        }
+    }
+    Ok(ranges)
+}
+
+/// Returns the contents of each of the newline-terminated object names in
+/// `objects`, using one `git cat-file --batch` process
+fn cat_blobs(dir: &Path, objects: &str) -> Result<Vec<Vec<u8>>, anyhow::Error> {
+    use std::io::Write;
+
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("running git cat-file")?;
+
+    // Write the requests from another thread so that neither of us blocks
+    // with a full pipe.
+    let mut stdin = child.stdin.take().unwrap();
+    let objects = objects.to_string();
+    let writer =
+        std::thread::spawn(move || stdin.write_all(objects.as_bytes()));
+    let output = child.wait_with_output().context("running git cat-file")?;
+    writer
+        .join()
+        .expect("git cat-file writer panicked")
+        .context("writing to git cat-file")?;
+    if !output.status.success() {
+        bail!(
+            "git cat-file: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    // Each response is "OBJECT TYPE SIZE\n", then SIZE bytes, then "\n".
+    let out = output.stdout;
+    let mut blobs = Vec::new();
+    let mut pos = 0;
+    while pos < out.len() {
+        let header_end = out[pos..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map(|n| pos + n)
+            .context("git cat-file: truncated output")?;
+        let header = String::from_utf8_lossy(&out[pos..header_end]);
+        let size: usize = header
+            .rsplit(' ')
+            .next()
+            .and_then(|size| size.parse().ok())
+            .with_context(|| {
+                format!("git cat-file: bad header {:?}", header)
+            })?;
+        let start = header_end + 1;
+        let end = start + size;
+        if end > out.len() {
+            bail!("git cat-file: truncated output");
+        }
+        blobs.push(out[start..end].to_vec());
+        pos = end + 1;
+    }
+
+    Ok(blobs)
+}
+
+/// Who last changed a line, according to `git blame`
+#[derive(Clone, Debug)]
+pub struct Blame {
+    /// abbreviated hash of the commit (all zeroes for a line that hasn't been
+    /// committed yet)
+    pub commit: String,
+    /// name of the commit's author ("Not Committed Yet" for a line that
+    /// hasn't been committed yet)
+    pub author: String,
+    /// when the commit was authored, as seconds since the Unix epoch
+    pub time: u64,
+}
+
+/// how many hex digits of a commit hash we keep in a [`Blame`]
+const ABBREV: usize = 10;
+
+/// Returns whether `dir` is inside a git repository's working tree
+pub fn in_work_tree(dir: &Path) -> bool {
+    git(dir, &["rev-parse", "--is-inside-work-tree"])
+        .is_ok_and(|output| output.trim() == "true")
+}
+
+/// Returns, for each line of `file` (in the working tree of the repository
+/// containing it), when that line was written, according to `git blame`
+///
+/// Element `i` is for line `i + 1`, as seconds since the Unix epoch.  Lines
+/// that haven't been committed yet count as having been written now.
+pub fn blame_times(file: &Path) -> Result<Vec<u64>, anyhow::Error> {
+    Ok(blame(file)?.into_iter().map(|b| b.time).collect())
+}
+
+/// Returns, for each line of `file` (in the working tree of the repository
+/// containing it), who last changed it, according to `git blame`
+///
+/// Element `i` is for line `i + 1`.  This runs `git blame` once for the whole
+/// file.
+pub fn blame(file: &Path) -> Result<Vec<Blame>, anyhow::Error> {
+    let dir = file.parent().filter(|d| !d.as_os_str().is_empty());
+    let name = file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("unsupported path {:?}", file.display()))?;
+    let output = git(
+        dir.unwrap_or(Path::new(".")),
+        &["blame", "--porcelain", "--", name],
+    )?;
+
+    // The porcelain format has a header line for each line of the file
+    // ("COMMIT ORIG_LINE FINAL_LINE ..."), followed by information about the
+    // commit (only the first time that commit appears), followed by the
+    // line's contents prefixed with a tab.
+    let mut commits: std::collections::BTreeMap<&str, (&str, u64)> =
+        std::collections::BTreeMap::new();
+    let mut lines = Vec::new();
+    let mut commit: Option<&str> = None;
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            let Some(c) = commit.take() else {
+                bail!("git blame: line without a header");
+            };
+            let (author, time) = commits.get(c).copied().unwrap_or(("", 0));
+            lines.push(Blame {
+                commit: c.chars().take(ABBREV).collect(),
+                author: author.to_string(),
+                time,
+            });
+        } else if let Some(time) = line.strip_prefix("author-time ") {
+            let time: u64 = time
+                .parse()
+                .with_context(|| format!("git blame: bad time {:?}", time))?;
+            if let Some(c) = commit {
+                commits.entry(c).or_default().1 = time;
+            }
+        } else if let Some(author) = line.strip_prefix("author ") {
+            if let Some(c) = commit {
+                commits.entry(c).or_default().0 = author;
+            }
+        } else if commit.is_none() {
+            commit = line.split(' ').next();
+        }
+    }
+
+    Ok(lines)
+}
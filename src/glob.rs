@@ -0,0 +1,180 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Shell-style glob patterns for matching paths
+//!
+//! Patterns match a whole "/"-separated path:
+//!
+//! * `*` matches any sequence of characters other than `/`
+//! * `**` matches any sequence of characters at all, and `**/` matches zero or
+//!   more whole directories (so `**/foo.rs` matches `foo.rs` and `a/b/foo.rs`)
+//! * `?` matches any one character other than `/`
+//! * `[abc]`, `[a-z]`, and `[!a-z]` match one character in (or not in) a set
+//! * `\` escapes the next character
+
+use anyhow::bail;
+use anyhow::Context;
+use std::path::Path;
+
+/// A compiled glob pattern
+#[derive(Clone, Debug)]
+pub struct Glob {
+    pattern: String,
+    tokens: Vec<Token>,
+}
+
+#[derive(Clone, Debug)]
+enum Token {
+    Literal(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    Star,
+    /// `**` (not followed by `/`)
+    AnyPath,
+    /// `**/`
+    AnyDirs,
+    /// `[...]`: inclusive ranges, and whether the set is negated
+    Class(Vec<(char, char)>, bool),
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob, anyhow::Error> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            i += 1;
+            match c {
+                '?' => tokens.push(Token::AnyChar),
+                '*' if chars.get(i) == Some(&'*') => {
+                    i += 1;
+                    if chars.get(i) == Some(&'/') {
+                        i += 1;
+                        tokens.push(Token::AnyDirs);
+                    } else {
+                        tokens.push(Token::AnyPath);
+                    }
+                }
+                '*' => tokens.push(Token::Star),
+                '[' => {
+                    let negated = matches!(chars.get(i), Some('!' | '^'));
+                    if negated {
+                        i += 1;
+                    }
+                    let mut ranges = Vec::new();
+                    let mut first = true;
+                    loop {
+                        let Some(&lo) = chars.get(i) else {
+                            bail!("invalid glob {:?}: unclosed \"[\"", pattern);
+                        };
+                        i += 1;
+                        if lo == ']' && !first {
+                            break;
+                        }
+                        first = false;
+                        if chars.get(i) == Some(&'-')
+                            && chars.get(i + 1).is_some_and(|c| *c != ']')
+                        {
+                            ranges.push((lo, chars[i + 1]));
+                            i += 2;
+                        } else {
+                            ranges.push((lo, lo));
+                        }
+                    }
+                    tokens.push(Token::Class(ranges, negated));
+                }
+                '\\' => {
+                    let Some(&escaped) = chars.get(i) else {
+                        bail!("invalid glob {:?}: trailing backslash", pattern);
+                    };
+                    i += 1;
+                    tokens.push(Token::Literal(escaped));
+                }
+                c => tokens.push(Token::Literal(c)),
+            }
+        }
+
+        Ok(Glob { pattern: pattern.to_string(), tokens })
+    }
+
+    /// Returns the pattern this was compiled from
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns whether `path` matches this pattern
+    ///
+    /// Path components are joined with "/" regardless of platform.
+    pub fn matches_path(&self, path: &Path) -> bool {
+        let path = path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        self.matches(&path)
+    }
+
+    /// Returns whether the "/"-separated path `path` matches this pattern
+    pub fn matches(&self, path: &str) -> bool {
+        let chars: Vec<char> = path.chars().collect();
+        matches_from(&self.tokens, &chars)
+    }
+}
+
+/// Loads the patterns in the file at `path`, one per line
+///
+/// Blank lines and lines starting with `#` are ignored, as is whitespace
+/// around each pattern.
+pub fn load_patterns(path: &Path) -> Result<Vec<Glob>, anyhow::Error> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("read {:?}", path.display()))?;
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            Glob::new(line).with_context(|| format!("{:?}", path.display()))
+        })
+        .collect()
+}
+
+fn matches_from(tokens: &[Token], text: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+
+    match token {
+        Token::Literal(c) => {
+            text.first() == Some(c) && matches_from(rest, &text[1..])
+        }
+        Token::AnyChar => {
+            text.first().is_some_and(|c| *c != '/')
+                && matches_from(rest, &text[1..])
+        }
+        Token::Class(ranges, negated) => {
+            text.first().is_some_and(|c| {
+                *c != '/'
+                    && ranges.iter().any(|(lo, hi)| lo <= c && c <= hi)
+                        != *negated
+            }) && matches_from(rest, &text[1..])
+        }
+        Token::Star => {
+            // Try every possible length of the run of non-"/" characters.
+            let run = text.iter().take_while(|c| **c != '/').count();
+            (0..=run).any(|n| matches_from(rest, &text[n..]))
+        }
+        Token::AnyPath => {
+            (0..=text.len()).any(|n| matches_from(rest, &text[n..]))
+        }
+        Token::AnyDirs => {
+            // Match zero directories, or everything up through any "/".
+            matches_from(rest, text)
+                || text.iter().enumerate().any(|(i, c)| {
+                    *c == '/' && matches_from(rest, &text[i + 1..])
+                })
+        }
+    }
+}
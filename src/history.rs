@@ -0,0 +1,108 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reports about how TODO-like comments changed over git history
+
+use anyhow::Context;
+use std::io::Write;
+use std::path::Path;
+use todos::find_kinds;
+use todos::git::git;
+use todos::Keywords;
+
+/// Writes a CSV time series to `out` with one row per commit, counting the
+/// TODO-like lines that each commit added and removed
+///
+/// We look at the most recent `max_count` commits in `range` (following only
+/// first parents, so that a merge counts just the changes it brought in),
+/// oldest first.  Each commit is compared against its first parent.  A line
+/// counts as TODO-like if it contains one of the keywords we'd look for in
+/// that file, the same way we'd find it in a comment.  We only look at the
+/// changed lines themselves, so a TODO in the middle of a block comment that's
+/// being edited is counted, but one that is merely near the edit is not.
+///
+/// Rows are flushed as they're written, since each one can take a while.
+pub fn blame_report(
+    out: &mut dyn Write,
+    repo: &Path,
+    range: &str,
+    max_count: usize,
+    keywords: &Keywords,
+) -> Result<(), anyhow::Error> {
+    let log = git(
+        repo,
+        &[
+            "log",
+            "--first-parent",
+            "--reverse",
+            &format!("--max-count={}", max_count),
+            "--format=%H%x09%cI",
+            range,
+            "--",
+        ],
+    )?;
+
+    writeln!(out, "commit,date,added,removed").context("writing report")?;
+    for entry in log.lines() {
+        let Some((commit, date)) = entry.split_once('\t') else {
+            continue;
+        };
+
+        let diff = git(
+            repo,
+            &[
+                "diff-tree",
+                "-p",
+                "-m",
+                "--first-parent",
+                "--root",
+                "--no-commit-id",
+                "--no-color",
+                "--unified=0",
+                commit,
+            ],
+        )?;
+
+        let (mut added, mut removed) = (0, 0);
+        let mut old_file: Option<&str> = None;
+        let mut current_file: Option<&str> = None;
+        for line in diff.lines() {
+            // File names look like "a/src/main.rs" (old side) and
+            // "b/src/main.rs" (new side), or "/dev/null" for the side that
+            // doesn't exist when a file is added or deleted.
+            if let Some(file) = line.strip_prefix("--- ") {
+                old_file = file.strip_prefix("a/");
+                continue;
+            }
+            if let Some(file) = line.strip_prefix("+++ ") {
+                current_file = file.strip_prefix("b/").or(old_file);
+                continue;
+            }
+
+            let Some(file) = current_file else {
+                continue;
+            };
+            if !file.ends_with(".rs") {
+                continue;
+            }
+
+            let keywords = keywords.for_path(Path::new(file));
+            if let Some(text) = line.strip_prefix('+') {
+                if !find_kinds(text, keywords).is_empty() {
+                    added += 1;
+                }
+            } else if let Some(text) = line.strip_prefix('-') {
+                if !find_kinds(text, keywords).is_empty() {
+                    removed += 1;
+                }
+            }
+        }
+
+        writeln!(out, "{},{},{},{}", commit, date, added, removed)
+            .and_then(|_| out.flush())
+            .context("writing report")?;
+    }
+
+    Ok(())
+}
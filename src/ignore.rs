@@ -0,0 +1,149 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Honoring `.gitignore`, `.ignore`, and `.git/info/exclude` during a walk
+//!
+//! Patterns follow gitignore(5): a pattern with a "/" (other than at the end)
+//! is relative to the directory of the file it's in, and one without can
+//! match at any depth below it; a trailing "/" matches only directories; and
+//! a leading "!" re-includes what an earlier pattern excluded.
+//!
+//! As with git and ripgrep, a file deeper in the tree takes precedence over
+//! those above it, `.ignore` takes precedence over `.gitignore` in the same
+//! directory, and `.git/info/exclude` comes last.  Ignore files in the
+//! directories above the root of the scan, up to the top of the git
+//! repository containing it, apply too.
+
+use crate::glob::Glob;
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// names of the ignore files we read in each directory, highest precedence
+/// first
+const IGNORE_FILES: &[&str] = &[".ignore", ".gitignore"];
+
+/// One pattern from an ignore file
+struct Rule {
+    glob: Glob,
+    /// whether the pattern started with "!"
+    negated: bool,
+    /// whether the pattern ended with "/"
+    dir_only: bool,
+}
+
+/// Decides which paths in a tree the ignore files exclude
+///
+/// Ignore files are read the first time a path below them is checked.
+pub struct IgnoreRules {
+    /// root of the tree, as given
+    root: PathBuf,
+    /// root of the tree, as an absolute path
+    absolute_root: PathBuf,
+    /// top of the git repository containing the tree (or the root of the
+    /// tree, if it's not in one)
+    top: PathBuf,
+    /// the patterns in `.git/info/exclude`
+    exclude: Vec<Rule>,
+    /// the patterns in each directory's ignore files, in `IGNORE_FILES`
+    /// order
+    dirs: HashMap<PathBuf, Vec<Vec<Rule>>>,
+}
+
+impl IgnoreRules {
+    /// Prepares to check paths in the tree at `root`
+    pub fn new(root: &Path) -> IgnoreRules {
+        let absolute_root =
+            std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+        let repo_top = absolute_root
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .map(Path::to_path_buf);
+        let exclude = repo_top
+            .as_ref()
+            .map(|top| load(&top.join(".git").join("info").join("exclude")))
+            .unwrap_or_default();
+        IgnoreRules {
+            root: root.to_path_buf(),
+            top: repo_top.unwrap_or_else(|| absolute_root.clone()),
+            absolute_root,
+            exclude,
+            dirs: HashMap::new(),
+        }
+    }
+
+    /// Returns whether `path` (as found by walking the tree, so starting with
+    /// its root) is ignored
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let path = self.absolute_root.join(relative);
+
+        // Check the directories from the one containing `path` up to the top.
+        // The first ignore file with a pattern matching `path` decides.
+        let top = self.top.clone();
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&top) {
+                break;
+            }
+            let rules =
+                self.dirs.entry(dir.to_path_buf()).or_insert_with(|| {
+                    IGNORE_FILES
+                        .iter()
+                        .map(|name| load(&dir.join(name)))
+                        .collect()
+                });
+            let within = path.strip_prefix(dir).unwrap_or(&path);
+            for rules in rules.iter() {
+                if let Some(ignored) = decide(rules, within, is_dir) {
+                    return ignored;
+                }
+            }
+        }
+
+        let within = path.strip_prefix(&top).unwrap_or(&path);
+        decide(&self.exclude, within, is_dir).unwrap_or(false)
+    }
+}
+
+/// Returns whether the last of `rules` that matches `path` (relative to the
+/// directory of the ignore file) ignores it, or `None` if none match
+fn decide(rules: &[Rule], path: &Path, is_dir: bool) -> Option<bool> {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| (is_dir || !rule.dir_only) && rule.glob.matches_path(path))
+        .map(|rule| !rule.negated)
+}
+
+/// Loads the patterns in the ignore file at `path`, which is treated as empty
+/// if it can't be read
+///
+/// Patterns that aren't valid globs are skipped.
+fn load(path: &Path) -> Vec<Rule> {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    contents
+        .lines()
+        .map(|line| line.trim_end())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (negated, pattern) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let (dir_only, pattern) = match pattern.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, pattern),
+            };
+            let pattern = match pattern.strip_prefix('/') {
+                Some(rest) => rest.to_string(),
+                None if pattern.contains('/') => pattern.to_string(),
+                None => format!("**/{}", pattern),
+            };
+            let glob = Glob::new(&pattern).ok()?;
+            Some(Rule { glob, negated, dir_only })
+        })
+        .collect()
+}
@@ -0,0 +1,115 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Looking up the state of issues referenced by comments
+//!
+//! We talk to the GitHub REST API using `curl(1)` rather than linking an HTTP
+//! client.  If `GITHUB_TOKEN` is set in the environment, we use it to
+//! authenticate (which raises the rate limit and allows access to private
+//! repositories).
+
+use crate::json;
+use anyhow::bail;
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+
+/// What we learned about an issue from the tracker
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IssueState {
+    Open,
+    Closed,
+    /// the tracker says there's no such issue
+    Missing,
+}
+
+/// Looks up (and caches) the state of issues in one GitHub repository
+pub struct IssueChecker {
+    /// "owner/name" of the repository
+    repo: String,
+    cache: BTreeMap<u64, IssueState>,
+}
+
+impl IssueChecker {
+    pub fn new(repo: &str) -> IssueChecker {
+        IssueChecker { repo: repo.to_string(), cache: BTreeMap::new() }
+    }
+
+    /// Returns the state of issue `number`, asking the tracker only the first
+    /// time we're asked about any particular issue
+    pub fn state(&mut self, number: u64) -> Result<IssueState, anyhow::Error> {
+        if let Some(state) = self.cache.get(&number) {
+            return Ok(*state);
+        }
+
+        let state = self.fetch(number)?;
+        self.cache.insert(number, state);
+        Ok(state)
+    }
+
+    fn fetch(&self, number: u64) -> Result<IssueState, anyhow::Error> {
+        let url = format!(
+            "https://api.github.com/repos/{}/issues/{}",
+            self.repo, number
+        );
+        let mut command = Command::new("curl");
+        command
+            .arg("--silent")
+            .arg("--show-error")
+            .arg("--location")
+            .arg("--header")
+            .arg("Accept: application/vnd.github+json")
+            .arg("--write-out")
+            .arg("\n%{http_code}");
+        // The token is passed on curl's stdin rather than its command line,
+        // where any other user could see it (e.g., with ps(1)).
+        let token = std::env::var("GITHUB_TOKEN").ok();
+        if token.is_some() {
+            command.arg("--header").arg("@-").stdin(Stdio::piped());
+        }
+        command.arg(&url).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("running curl for {}", url))?;
+        if let Some(token) = token {
+            let mut stdin = child.stdin.take().expect("stdin is piped");
+            writeln!(stdin, "Authorization: Bearer {}", token)
+                .with_context(|| format!("writing to curl for {}", url))?;
+        }
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("running curl for {}", url))?;
+        if !output.status.success() {
+            bail!(
+                "fetching {}: curl failed: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        // We asked curl to append the HTTP status on its own line.
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (body, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+        match status.trim() {
+            "200" => (),
+            "404" | "410" => return Ok(IssueState::Missing),
+            other => {
+                bail!("fetching {}: unexpected HTTP status {}", url, other)
+            }
+        }
+
+        let issue =
+            json::parse(body).with_context(|| format!("fetching {}", url))?;
+        match issue.get("state").and_then(json::Value::as_str) {
+            Some("open") => Ok(IssueState::Open),
+            Some("closed") => Ok(IssueState::Closed),
+            other => {
+                bail!("fetching {}: unexpected issue state {:?}", url, other)
+            }
+        }
+    }
+}
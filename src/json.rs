@@ -0,0 +1,244 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Minimal JSON support
+//!
+//! We only deal with small documents (API responses and our own output), so
+//! this is a simple recursive-descent parser into an in-memory tree.
+
+use anyhow::anyhow;
+use anyhow::bail;
+use std::collections::BTreeMap;
+
+/// A parsed JSON value
+#[derive(Debug)]
+pub enum Value {
+    Null,
+    // Nothing we read has booleans in it yet, but we keep their value for
+    // whatever does.
+    #[allow(dead_code)]
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// If this is an object, returns the value of its property `key`
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(properties) => properties.get(key),
+            _ => None,
+        }
+    }
+
+    /// If this is a string, returns it
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Parses `input` as a single JSON document
+pub fn parse(input: &str) -> Result<Value, anyhow::Error> {
+    let mut parser = Parser { input: input.as_bytes(), pos: 0 };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos != parser.input.len() {
+        return Err(parser.error("trailing characters after JSON value"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!("parsing JSON: {} at byte {}", message, self.pos)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.input.get(self.pos).is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), anyhow::Error> {
+        if self.input[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {:?}", literal)))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, anyhow::Error> {
+        self.skip_whitespace();
+        match self.input.get(self.pos) {
+            None => Err(self.error("unexpected end of input")),
+            Some(b'n') => self.expect("null").map(|_| Value::Null),
+            Some(b't') => self.expect("true").map(|_| Value::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Value::Bool(false)),
+            Some(b'"') => self.parse_string().map(Value::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.input.get(self.pos) == Some(&b']') {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                loop {
+                    items.push(self.parse_value()?);
+                    self.skip_whitespace();
+                    match self.input.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Value::Array(items));
+                        }
+                        _ => return Err(self.error("expected \",\" or \"]\"")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut properties = BTreeMap::new();
+                self.skip_whitespace();
+                if self.input.get(self.pos) == Some(&b'}') {
+                    self.pos += 1;
+                    return Ok(Value::Object(properties));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.input.get(self.pos) != Some(&b'"') {
+                        return Err(self.error("expected property name"));
+                    }
+                    let key = self.parse_string()?;
+                    self.skip_whitespace();
+                    self.expect(":")?;
+                    let value = self.parse_value()?;
+                    properties.insert(key, value);
+                    self.skip_whitespace();
+                    match self.input.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(Value::Object(properties));
+                        }
+                        _ => return Err(self.error("expected \",\" or \"}\"")),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                let start = self.pos;
+                while self
+                    .input
+                    .get(self.pos)
+                    .is_some_and(|b| b.is_ascii_digit() || b"+-.eE".contains(b))
+                {
+                    self.pos += 1;
+                }
+                std::str::from_utf8(&self.input[start..self.pos])
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .map(Value::Number)
+                    .ok_or_else(|| self.error("invalid number"))
+            }
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    /// Parses a string literal, starting at its opening quote
+    fn parse_string(&mut self) -> Result<String, anyhow::Error> {
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let Some(b) = self.input.get(self.pos).copied() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let Some(escape) = self.input.get(self.pos).copied() else {
+                        return Err(self.error("unterminated string"));
+                    };
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                b => bytes.push(b),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    /// Parses the hex digits of a `\u` escape (and its low surrogate, if it
+    /// turns out to be a high surrogate)
+    fn parse_unicode_escape(&mut self) -> Result<char, anyhow::Error> {
+        let first = self.parse_hex4()?;
+        let code = if (0xd800..0xdc00).contains(&first) {
+            self.expect("\\u")?;
+            let second = self.parse_hex4()?;
+            if !(0xdc00..0xe000).contains(&second) {
+                bail!("parsing JSON: invalid surrogate pair");
+            }
+            0x10000 + ((first - 0xd800) << 10) + (second - 0xdc00)
+        } else {
+            first
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, anyhow::Error> {
+        let digits = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok())
+            .ok_or_else(|| self.error("invalid \\u escape"))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+}
+
+/// Returns `s` as a JSON string literal
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                quoted.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
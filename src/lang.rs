@@ -0,0 +1,76 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Comment syntaxes of the languages we can scan
+//!
+//! Rust is what we really understand: we skip over its string literals when
+//! looking for comments after code, and we keep track of the items that
+//! comments are attached to and inside of.  For other languages, we only
+//! know what their comments look like.
+
+use std::path::Path;
+
+/// What comments look like in some language
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Syntax {
+    /// what starts a line comment (e.g., "//" or "#")
+    pub line_comment: &'static str,
+    /// what starts and ends a block comment, if the language has them
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// whether block comments nest
+    pub nested_blocks: bool,
+    /// whether this is Rust (see the module documentation)
+    pub rust: bool,
+}
+
+impl Syntax {
+    /// Rust
+    pub const RUST: Syntax = Syntax {
+        line_comment: "//",
+        block_comment: Some(("/*", "*/")),
+        nested_blocks: true,
+        rust: true,
+    };
+    /// C and the many languages that borrowed its comments
+    pub const C: Syntax = Syntax {
+        line_comment: "//",
+        block_comment: Some(("/*", "*/")),
+        nested_blocks: false,
+        rust: false,
+    };
+    /// shell, Python, and other languages with "#" comments
+    pub const HASH: Syntax = Syntax {
+        line_comment: "#",
+        block_comment: None,
+        nested_blocks: false,
+        rust: false,
+    };
+}
+
+/// Each language we know: its name (for `--lang`), the file extensions it
+/// uses, and its comment syntax
+pub const LANGUAGES: &[(&str, &[&str], Syntax)] = &[
+    ("rust", &["rs"], Syntax::RUST),
+    ("c", &["c", "h"], Syntax::C),
+    ("cpp", &["cc", "cpp", "cxx", "hh", "hpp", "hxx"], Syntax::C),
+    ("javascript", &["js", "mjs", "cjs"], Syntax::C),
+    ("python", &["py"], Syntax::HASH),
+    ("shell", &["sh", "bash"], Syntax::HASH),
+];
+
+/// Returns the comment syntax for the language named `name` (as in
+/// [`LANGUAGES`])
+pub fn syntax_named(name: &str) -> Option<Syntax> {
+    LANGUAGES.iter().find(|(n, _, _)| *n == name).map(|(_, _, syntax)| *syntax)
+}
+
+/// Returns the comment syntax for files named like `path`, judging by the
+/// extension, if it's one we know
+pub fn syntax_for_path(path: &Path) -> Option<Syntax> {
+    let extension = path.extension()?;
+    LANGUAGES
+        .iter()
+        .find(|(_, extensions, _)| extensions.iter().any(|e| extension == *e))
+        .map(|(_, _, syntax)| *syntax)
+}
@@ -0,0 +1,2147 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Find and summarize TODO-like comments in Rust code (and, with less
+//! understanding, in a few other languages; see [`lang`])
+//!
+//! [`scan()`] walks a tree and lazily yields each TODO-like [`Comment`] that it
+//! finds, and [`scan_with()`] does the same walk but hands each one to a
+//! callback.  [`scan_str()`] finds them in a string of Rust source instead,
+//! and [`CommentIterator`] yields every comment in one, TODO-like or not.
+//! [`CommentTracker`] collects comments, grouped by label.  The `todos`
+//! command-line tool is built on these.
+
+use anyhow::Context;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+// These are public for the `todos` binary, not as part of the library's API.
+// The types from them that appear in the API are re-exported below.
+#[doc(hidden)]
+pub mod git;
+#[doc(hidden)]
+pub mod glob;
+mod ignore;
+pub mod lang;
+mod markdown;
+mod parser;
+#[doc(hidden)]
+pub mod regex;
+
+pub use git::Blame;
+pub use glob::Glob;
+pub use parser::find_macro_calls;
+pub use parser::source_lines;
+pub use parser::CommentIterator;
+pub use parser::Item;
+pub use parser::MacroCall;
+pub use parser::ParseWarning;
+pub use parser::RawComment;
+pub use parser::DEFERRED_WORK_MACROS;
+pub use regex::Regex;
+
+use ignore::IgnoreRules;
+use lang::Syntax;
+
+/// keywords we look for when nothing says otherwise
+pub const DEFAULT_KEYWORDS: &[&str] = &["XXX", "FIXME", "TODO"];
+
+/// build output directories we skip at the root of the tree by default
+///
+/// "target" is in [`DEFAULT_SKIP_DIRS`] too, so that it's skipped wherever it
+/// is.  One at the root is reported as build output, and it's scanned only if
+/// neither list has it.
+pub const DEFAULT_BUILD_DIRS: &[&str] = &["target"];
+
+/// directories we skip wherever they are in the tree by default (see
+/// [`DEFAULT_BUILD_DIRS`])
+pub const DEFAULT_SKIP_DIRS: &[&str] = &["target"];
+
+/// Settings that control a scan
+#[derive(Clone)]
+pub struct Config {
+    /// which keywords to look for in which files
+    pub keywords: Keywords,
+    /// names of build output directories to skip at the root of the tree
+    pub build_dirs: Vec<String>,
+    /// names of directories to skip wherever they are in the tree (e.g.,
+    /// "node_modules"; by default, [`DEFAULT_SKIP_DIRS`])
+    pub skip_dirs: Vec<String>,
+    /// skip files and directories whose path (relative to the root of the
+    /// tree) matches any of these.  A pattern without a "/" can also match
+    /// just the file name, anywhere in the tree.
+    pub exclude: Vec<Glob>,
+    /// skip files and directories excluded by `.gitignore`, `.ignore`, and
+    /// `.git/info/exclude` files
+    pub ignore_files: bool,
+    /// print progress messages (files being read or skipped) to stderr
+    pub progress: bool,
+    /// also report invocations of [`DEFERRED_WORK_MACROS`] like `todo!()`
+    pub scan_macros: bool,
+    /// match keywords regardless of (ASCII) case, uppercasing them in labels
+    /// so that case variants are grouped together (e.g., "todo" and "Todo"
+    /// are both reported as "TODO")
+    pub ignore_case: bool,
+    /// end a run of `//` comment lines at a line that's less indented than
+    /// the first (see [`CommentIterator::split_on_dedent()`])
+    pub split_on_dedent: bool,
+    /// keep the whitespace around comments' lines, so that their text is as
+    /// it is in the source (see [`CommentIterator::preserve_indent()`])
+    pub preserve_indent: bool,
+    /// skip files that were last modified before this time
+    pub modified_since: Option<SystemTime>,
+    /// skip files smaller than this many bytes
+    pub min_filesize: Option<u64>,
+    /// skip files larger than this many bytes
+    pub max_filesize: Option<u64>,
+    /// also look for unchecked checklist items in Markdown (".md") files
+    pub scan_markdown: bool,
+    /// how many lines of source before and after each comment to keep with
+    /// it (see [`Comment::context_before`])
+    pub context_lines: usize,
+    /// descend into symlinks to directories (symlinks to files are always
+    /// scanned)
+    pub follow_links: bool,
+    /// don't descend into directories on other file systems than the root
+    pub same_file_system: bool,
+    /// how many levels of directories below the root to look in, if
+    /// limited: with 1, only the files directly in the root are scanned
+    pub max_depth: Option<usize>,
+    /// treat a file with a malformed block comment (see [`ParseWarning`]) as
+    /// an error: leave out its comments and report each problem as a
+    /// [`MalformedComment`] rather than as a plain warning
+    pub strict_comments: bool,
+    /// end the scan at the first file or directory that can't be read,
+    /// rather than yielding the error and going on (see [`Scan::failure()`])
+    pub strict_files: bool,
+    /// how many threads [`scan_with()`] uses to read and parse files (by
+    /// default, one per CPU)
+    pub threads: Option<usize>,
+    /// comment syntax of files whose extension isn't in [`lang::LANGUAGES`]
+    /// (like a script named on the command line, or standard input); Rust's
+    /// if unset
+    pub lang: Option<Syntax>,
+    /// regular expressions that also find TODO-like comments, besides the
+    /// keywords.  Each match is labeled with the part matched by the group
+    /// named "label", if there is one, or else the whole match.
+    pub patterns: Vec<Regex>,
+}
+
+impl Config {
+    /// Returns whether the file or directory at `path` (relative to the root
+    /// of the tree) matches one of the `exclude` patterns
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.excluded_by(path).is_some()
+    }
+
+    /// Returns the first of the `exclude` patterns that the file or directory
+    /// at `path` (relative to the root of the tree) matches, if any
+    pub fn excluded_by(&self, path: &Path) -> Option<&Glob> {
+        self.exclude.iter().find(|glob| {
+            glob.matches_path(path)
+                || (!glob.as_str().contains('/')
+                    && path
+                        .file_name()
+                        .is_some_and(|n| glob.matches(&n.to_string_lossy())))
+        })
+    }
+
+    /// Returns whether we look for comments in files named like `path`:
+    /// files in the languages in [`lang::LANGUAGES`], plus Markdown files if
+    /// `scan_markdown` is set
+    pub fn wants_file(&self, path: &Path) -> bool {
+        lang::syntax_for_path(path).is_some()
+            || (self.scan_markdown && is_markdown(path))
+    }
+}
+
+/// Returns whether `path` names a Markdown file
+fn is_markdown(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "md")
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            keywords: Keywords::default(),
+            build_dirs: DEFAULT_BUILD_DIRS
+                .iter()
+                .map(|d| d.to_string())
+                .collect(),
+            skip_dirs: DEFAULT_SKIP_DIRS
+                .iter()
+                .map(|d| d.to_string())
+                .collect(),
+            exclude: Vec::new(),
+            ignore_files: true,
+            progress: false,
+            ignore_case: false,
+            split_on_dedent: false,
+            preserve_indent: false,
+            scan_macros: false,
+            modified_since: None,
+            min_filesize: None,
+            max_filesize: None,
+            scan_markdown: false,
+            context_lines: 0,
+            follow_links: false,
+            same_file_system: false,
+            max_depth: None,
+            strict_files: false,
+            strict_comments: false,
+            threads: None,
+            lang: None,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// Describes which TODO-like keywords to look for in which files
+#[derive(Clone)]
+pub struct Keywords {
+    /// keywords to look for in files not covered by any scope.  A word
+    /// matches if it starts with one of these, so none should be empty.
+    pub default: Vec<String>,
+    /// keywords to look for instead in files whose path (relative to the root
+    /// of the tree) matches a particular pattern.  When several of these
+    /// match, the last one wins.
+    pub scopes: Vec<(Glob, Vec<String>)>,
+}
+
+impl Default for Keywords {
+    fn default() -> Keywords {
+        Keywords {
+            default: DEFAULT_KEYWORDS.iter().map(|k| k.to_string()).collect(),
+            scopes: Vec::new(),
+        }
+    }
+}
+
+impl Keywords {
+    /// Returns the keywords to look for in the file at `path`, which is
+    /// relative to the root of the tree
+    pub fn for_path(&self, path: &Path) -> &[String] {
+        self.scopes
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.matches_path(path))
+            .map(|(_, keywords)| keywords)
+            .unwrap_or(&self.default)
+    }
+}
+
+/// What sort of thing a [`Comment`] is
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CommentStyle {
+    /// an actual comment
+    Comment(CommentKind),
+    /// an invocation of one of the [`DEFERRED_WORK_MACROS`], like `todo!()`,
+    /// with the message given to it (if any)
+    Macro { message: Option<String> },
+    /// an unchecked checklist item in a Markdown file (see
+    /// [`Config::scan_markdown`])
+    Markdown,
+}
+
+/// What sort of comment a [`CommentStyle::Comment`] is
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CommentKind {
+    /// a run of line comments (`//` or `#`)
+    Line,
+    /// a block comment (`/* ... */`)
+    Block,
+    /// a run of doc comment lines (`///` or `//!`)
+    DocLine,
+    /// a doc block comment (`/** ... */` or `/*! ... */`)
+    DocBlock,
+}
+
+impl CommentKind {
+    /// Returns whether this is a doc comment, outer or inner
+    pub fn is_doc(self) -> bool {
+        matches!(self, CommentKind::DocLine | CommentKind::DocBlock)
+    }
+}
+
+/// Represents a particular comment found in a particular file
+///
+/// A comment with several labels (e.g., "TODO-security" and "TODO-coverage")
+/// is reported once for each label.
+///
+/// With `Config::scan_macros`, this can also be a macro invocation like
+/// `todo!("...")`, whose label is the macro's name (e.g., "todo!").
+#[derive(Clone, Debug)]
+pub struct Comment {
+    /// text of the comment, including comment markers (or, for a macro, the
+    /// source of the whole invocation)
+    pub contents: String,
+    /// path to the file containing the comment
+    pub file: String,
+    /// line number (starting from 1) where the comment starts (or, if
+    /// `column` is set, where that occurrence of the label is)
+    pub line: usize,
+    /// line number (starting from 1) where the comment ends (or, if `column`
+    /// is set, `line`)
+    pub end_line: usize,
+    /// the TODO-like label that we found in it (e.g., "TODO-security")
+    pub label: String,
+    /// issue number referenced by this comment's label (e.g., "TODO#123")
+    pub issue: Option<u64>,
+    /// year that this comment's label says it should be dealt with by (e.g.,
+    /// "TODO(2025)")
+    pub year: Option<u32>,
+    /// who this comment's label says is responsible for it (e.g.,
+    /// "TODO(alice)")
+    pub assignee: Option<String>,
+    /// the item (function, struct, etc.) that immediately follows the comment
+    pub item: Option<Item>,
+    /// tag assigned to this comment out-of-band (see `--tags`)
+    pub tag: Option<String>,
+    /// whether this is a comment or a macro invocation
+    pub style: CommentStyle,
+    /// the innermost item that the comment appears inside of, if we could
+    /// tell (e.g., "fn main" or "impl Iterator for Foo")
+    pub enclosing: Option<String>,
+    /// identifies this comment across runs (see [`stable_id()`])
+    pub id: String,
+    /// whether the comment is followed directly by code (see
+    /// [`RawComment::attached`]).  A macro invocation is code itself, so it
+    /// always counts as attached.
+    pub attached: bool,
+    /// line and column (both starting from 1, the column in characters) of
+    /// each place the label appears in the comment
+    pub occurrences: Vec<(usize, usize)>,
+    /// column of the label on `line`, if this describes just one of its
+    /// occurrences (see [`Comment::split_occurrences()`])
+    pub column: Option<usize>,
+    /// lines of source just before the comment (see `Config::context_lines`)
+    pub context_before: Vec<String>,
+    /// lines of source just after the comment
+    pub context_after: Vec<String>,
+    /// who last changed `line` and in which commit, if that's been looked up
+    /// (see [`git::blame()`])
+    pub blame: Option<Blame>,
+}
+
+impl Comment {
+    /// Returns the line and column (both starting from 1) where the label
+    /// first appears, for jumping to it in an editor
+    ///
+    /// This is the same as `line` and `column` for a comment describing one
+    /// occurrence, and falls back to the start of the comment's first line if
+    /// we don't know where the label is.
+    pub fn position(&self) -> (usize, usize) {
+        match (self.column, self.occurrences.first()) {
+            (Some(column), _) => (self.line, column),
+            (None, Some(&position)) => position,
+            (None, None) => (self.line, 1),
+        }
+    }
+
+    /// Returns a copy of this comment for each place its label appears in
+    /// it, with `line` and `column` giving that place
+    pub fn split_occurrences(&self) -> Vec<Comment> {
+        self.occurrences
+            .iter()
+            .map(|&(line, column)| Comment {
+                line,
+                end_line: line,
+                column: Some(column),
+                occurrences: vec![(line, column)],
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// Returns the first line of the comment's text, minus its comment marker
+    /// and any surrounding whitespace
+    ///
+    /// Lines with nothing but a comment marker (like a "/*" on its own) are
+    /// skipped, as in [`Comment::stripped_lines()`], so this is only empty if
+    /// the whole comment is.
+    pub fn first_line(&self) -> &str {
+        self.stripped_lines()
+            .into_iter()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or("")
+    }
+
+    /// Returns the lines of the comment without their comment markers or
+    /// surrounding whitespace, for showing just the prose
+    ///
+    /// That means "//", "///", "//!", or "#" at the start of each line of a
+    /// line comment, and for a block comment, the opening "/*" (or "/**" or
+    /// "/*!"), the closing "*/", and any "*" at the start of the lines after
+    /// the first.  A "*/" anywhere but the very end is part of the text.  Lines
+    /// left empty at the start and end (like a "/*" on its own) are dropped.
+    /// The lines of a macro invocation or Markdown item are returned as they
+    /// are.
+    pub fn stripped_lines<'a>(&'a self) -> Vec<&'a str> {
+        let lines: Vec<&str> = self.contents.lines().collect();
+        if !matches!(self.style, CommentStyle::Comment(_)) {
+            return lines;
+        }
+
+        let strip = |line: &'a str, markers: &[&str]| -> &'a str {
+            markers
+                .iter()
+                .find_map(|marker| line.strip_prefix(marker))
+                .unwrap_or(line)
+        };
+        let block = lines.first().is_some_and(|l| l.starts_with("/*"));
+        let last = lines.len().saturating_sub(1);
+        let mut stripped: Vec<&str> = lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let mut line = line.trim();
+                if !block {
+                    line = strip(line, &["///", "//!", "//", "#"]);
+                } else {
+                    if i == 0 {
+                        line = strip(line, &["/**", "/*!", "/*"]);
+                    }
+                    if i == last {
+                        line = line.strip_suffix("*/").unwrap_or(line);
+                    }
+                    if i > 0 {
+                        line = line.trim_start();
+                        line = line.strip_prefix('*').unwrap_or(line);
+                    }
+                }
+                line.trim()
+            })
+            .collect();
+        while stripped.last().is_some_and(|l| l.is_empty()) {
+            stripped.pop();
+        }
+        let blank = stripped.iter().take_while(|l| l.is_empty()).count();
+        stripped.split_off(blank)
+    }
+
+    /// Returns a hash of this comment's text (64-bit FNV-1a, as 16 hex digits)
+    /// that doesn't depend on how it's formatted
+    ///
+    /// The text is taken without comment markers (see
+    /// [`Comment::stripped_lines()`]) and with whitespace normalized, so that
+    /// rewrapping the comment, reindenting it, or turning `//` lines into a
+    /// block comment doesn't change the hash.  Unlike [`stable_id()`], this
+    /// leaves out the label, so each label of a comment has the same hash.
+    pub fn content_hash(&self) -> String {
+        let mut hash = Fnv1a::new();
+        for word in
+            self.stripped_lines().iter().flat_map(|l| l.split_whitespace())
+        {
+            hash.add(word.as_bytes());
+            hash.add(b" ");
+        }
+        hash.hex()
+    }
+
+    /// Returns whether this comment's label appears in an outer doc comment
+    /// (`///` or `/**`), which rustdoc attaches to the item that follows it
+    ///
+    /// A run of line comments may mix `///` lines with other kinds, so for
+    /// those we look at the lines containing the label.  Inner doc comments
+    /// (`//!` and `/*!`) document the enclosing item instead, which we don't
+    /// keep track of, so they don't count here.
+    pub fn is_doc(&self) -> bool {
+        let first = self.contents.lines().next().unwrap_or("");
+        if first.starts_with("/*") {
+            return first.starts_with("/**") && !first.starts_with("/**/");
+        }
+
+        self.contents.lines().any(|line| {
+            let line = line.trim_start();
+            line.starts_with("///")
+                && !line.starts_with("////")
+                && line
+                    .split_whitespace()
+                    .any(|w| w.strip_suffix(':').unwrap_or(w) == self.label)
+        })
+    }
+
+    /// Returns whether anything meaningful follows the label, as opposed to
+    /// a bare "// TODO" or "// FIXME:"
+    ///
+    /// Comment markers and punctuation don't count.  For a macro invocation,
+    /// this is whether it has a message.
+    pub fn has_description(&self) -> bool {
+        let meaningful = |s: &str| s.chars().any(char::is_alphanumeric);
+        if let CommentStyle::Macro { message } = &self.style {
+            return message.as_deref().is_some_and(meaningful);
+        }
+
+        let words: Vec<&str> = self
+            .contents
+            .split_whitespace()
+            .filter(|w| !is_comment_marker(w))
+            .collect();
+        let label: Vec<&str> = self.label.split_whitespace().collect();
+        let matches = |(w, l): (&&str, &&str)| {
+            w == l || w.trim_end_matches([':', ',', '.', ';', '!', '?']) == *l
+        };
+        match words
+            .windows(label.len())
+            .position(|window| window.iter().zip(&label).all(matches))
+        {
+            Some(i) => words[i + label.len()..].iter().any(|w| meaningful(w)),
+            None => true,
+        }
+    }
+
+    /// If this is a doc comment for a plain `pub` item, returns that item
+    pub fn public_doc_item(&self) -> Option<&Item> {
+        self.item.as_ref().filter(|item| item.public && self.is_doc())
+    }
+}
+
+/// Tracks all TODO-like comments found in our search, grouped by a "kind"
+///
+/// The kind is basically whatever whitespace-separated word we identified as
+/// TODO-like.  This might be "TODO" or "XXX" or "TODO-security" or whatever.
+///
+/// If `max_per_label` is set, only that many comments are kept for each label.
+/// The rest are just counted, so that [`CommentTracker::count()`] and
+/// [`CommentTracker::total()`] stay accurate while memory use stays bounded.
+#[derive(Default)]
+pub struct CommentTracker {
+    pub comments_by_kind: BTreeMap<String, Vec<Comment>>,
+    /// maximum number of comments to keep for each label
+    pub max_per_label: Option<usize>,
+    /// number of comments for each label that we counted but didn't keep
+    pub truncated: BTreeMap<String, usize>,
+}
+
+impl CommentTracker {
+    pub fn new() -> CommentTracker {
+        CommentTracker::default()
+    }
+
+    /// Records `comment` under its label
+    pub fn add(&mut self, comment: Comment) {
+        let comments =
+            self.comments_by_kind.entry(comment.label.clone()).or_default();
+        if self.max_per_label.is_some_and(|max| comments.len() >= max) {
+            *self.truncated.entry(comment.label).or_default() += 1;
+        } else {
+            comments.push(comment);
+        }
+    }
+
+    /// Returns how many comments we've seen with label `label`, including any
+    /// that we didn't keep
+    pub fn count(&self, label: &str) -> usize {
+        self.comments_by_kind.get(label).map_or(0, Vec::len)
+            + self.truncated.get(label).copied().unwrap_or(0)
+    }
+
+    /// Returns how many distinct comments we've seen in all, including any
+    /// that we didn't keep
+    ///
+    /// A comment with several labels is recorded (and counted by
+    /// [`CommentTracker::count()`]) once for each of them, but it only counts
+    /// once here.  Comments that we didn't keep can't be compared, so each of
+    /// those counts separately.
+    pub fn total(&self) -> usize {
+        let distinct: BTreeSet<(&str, usize, Option<usize>, &str)> = self
+            .comments_by_kind
+            .values()
+            .flatten()
+            .map(|c| (c.file.as_str(), c.line, c.column, c.contents.as_str()))
+            .collect();
+        distinct.len() + self.truncated.values().sum::<usize>()
+    }
+
+    /// Returns how many of the comments kept are in each file, counting each
+    /// comment once as in [`CommentTracker::total()`]
+    pub fn counts_by_file(&self) -> BTreeMap<&str, usize> {
+        let distinct: BTreeSet<(&str, usize, Option<usize>, &str)> = self
+            .comments_by_kind
+            .values()
+            .flatten()
+            .map(|c| (c.file.as_str(), c.line, c.column, c.contents.as_str()))
+            .collect();
+        let mut counts = BTreeMap::new();
+        for (file, ..) in distinct {
+            *counts.entry(file).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Returns how many of the comments kept reference an issue (under any of
+    /// their labels) and how many don't, counting each comment once as in
+    /// [`CommentTracker::total()`]
+    pub fn issue_counts(&self) -> (usize, usize) {
+        let mut linked: BTreeMap<(&str, usize, Option<usize>, &str), bool> =
+            BTreeMap::new();
+        for c in self.comments_by_kind.values().flatten() {
+            let key = (c.file.as_str(), c.line, c.column, c.contents.as_str());
+            *linked.entry(key).or_default() |= c.issue.is_some();
+        }
+        let with = linked.values().filter(|l| **l).count();
+        (with, linked.len() - with)
+    }
+
+    /// Sorts the comments under each label by file and then by line
+    ///
+    /// The order they're found in depends on the order of the walk (and with
+    /// several trees, of the trees), so reports sort them to come out the same
+    /// everywhere.
+    pub fn sort(&mut self) {
+        for comments in self.comments_by_kind.values_mut() {
+            comments.sort_by(|a, b| {
+                (&a.file, a.line, a.column).cmp(&(&b.file, b.line, b.column))
+            });
+        }
+    }
+
+    /// Removes every comment for which `keep(label, comment)` returns false,
+    /// along with any labels that are left with no comments at all
+    ///
+    /// Comments that were counted but not kept (see `max_per_label`) can't be
+    /// examined, so they're unaffected.
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&str, &Comment) -> bool,
+    {
+        self.comments_by_kind.retain(|label, comments| {
+            comments.retain(|c| keep(label, c));
+            !comments.is_empty()
+        });
+    }
+}
+
+/// Describes how a report was produced, so that a saved report documents
+/// itself
+#[derive(Clone, Debug)]
+pub struct ReportHeader {
+    /// version of this tool
+    pub version: String,
+    /// what was scanned (paths, or a name like `<stdin>`)
+    pub roots: Vec<String>,
+    /// keywords looked for outside of any scope
+    pub keywords: Vec<String>,
+    /// patterns of `Keywords::scopes`, with the keywords for each
+    pub scopes: Vec<(String, Vec<String>)>,
+    /// exclude patterns (see `Config::exclude`)
+    pub exclude: Vec<String>,
+}
+
+impl ReportHeader {
+    /// Describes a scan of `roots` with `config`
+    pub fn new(roots: Vec<String>, config: &Config) -> ReportHeader {
+        ReportHeader {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            roots,
+            keywords: config.keywords.default.clone(),
+            scopes: config
+                .keywords
+                .scopes
+                .iter()
+                .map(|(glob, keywords)| {
+                    (glob.as_str().to_string(), keywords.clone())
+                })
+                .collect(),
+            exclude: config
+                .exclude
+                .iter()
+                .map(|glob| glob.as_str().to_string())
+                .collect(),
+        }
+    }
+}
+
+/// A block comment in a file that [`CommentIterator`] couldn't make sense of
+/// (see [`ParseWarning`]), reported as an error with
+/// `Config::strict_comments`
+#[derive(Clone, Debug)]
+pub struct MalformedComment {
+    /// path to the file containing the comment
+    pub file: String,
+    /// line number (starting from 1) where the comment starts, or where the
+    /// stray end of one is
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for MalformedComment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for MalformedComment {}
+
+/// Counts of what happened during a scan
+///
+/// With several threads, the read and parse times are totals across them.
+#[derive(Clone, Debug, Default)]
+pub struct ScanStats {
+    /// source (and Markdown) files that we read
+    pub files_scanned: usize,
+    /// files that we skipped because they hadn't been modified since
+    /// `Config::modified_since`
+    pub files_unmodified: usize,
+    /// files that we skipped because their size was outside the range
+    /// given by `Config::min_filesize` and `Config::max_filesize`
+    pub files_wrong_size: usize,
+    /// time spent walking the directory tree
+    pub walk_time: Duration,
+    /// time spent opening and reading files
+    pub read_time: Duration,
+    /// time spent finding comments in the files we read
+    pub parse_time: Duration,
+}
+
+impl std::ops::AddAssign for ScanStats {
+    /// Adds the counts and times from another scan (e.g., of another tree)
+    fn add_assign(&mut self, other: ScanStats) {
+        self.files_scanned += other.files_scanned;
+        self.files_unmodified += other.files_unmodified;
+        self.files_wrong_size += other.files_wrong_size;
+        self.walk_time += other.walk_time;
+        self.read_time += other.read_time;
+        self.parse_time += other.parse_time;
+    }
+}
+
+/// Iterator over the TODO-like comments in a tree, returned by [`scan()`]
+pub struct Scan<'a> {
+    root: &'a Path,
+    config: &'a Config,
+    entries: Box<
+        dyn Iterator<Item = Result<walkdir::DirEntry, walkdir::Error>> + 'a,
+    >,
+    /// comments (and warnings) from the current file that we haven't
+    /// yielded yet
+    pending: std::vec::IntoIter<Result<Comment, anyhow::Error>>,
+    stats: ScanStats,
+    /// with `config.strict_files`, the error that ended the scan
+    failure: Option<anyhow::Error>,
+}
+
+impl<'a> Scan<'a> {
+    /// Returns counts of what the scan has done so far
+    pub fn stats(&self) -> &ScanStats {
+        &self.stats
+    }
+
+    /// With `config.strict_files`, returns the error reading a file or
+    /// directory that ended the scan early, if any
+    ///
+    /// This error is not also yielded by the iterator.
+    pub fn failure(&self) -> Option<&anyhow::Error> {
+        self.failure.as_ref()
+    }
+}
+
+impl<'a> Iterator for Scan<'a> {
+    type Item = Result<Comment, anyhow::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(result) = self.pending.next() {
+                return Some(result);
+            }
+            if self.failure.is_some() {
+                return None;
+            }
+
+            let start = Instant::now();
+            let maybe_entry = self.entries.next();
+            self.stats.walk_time += start.elapsed();
+            let maybe_entry = maybe_entry?;
+            let mut warnings = Vec::new();
+            match scan_file(
+                self.root,
+                self.config,
+                &mut self.stats,
+                maybe_entry,
+                &mut warnings,
+            ) {
+                Ok(comments) => {
+                    self.pending = warnings
+                        .into_iter()
+                        .map(Err)
+                        .chain(comments.into_iter().map(Ok))
+                        .collect::<Vec<_>>()
+                        .into_iter()
+                }
+                Err(error) if self.config.strict_files => {
+                    self.failure = Some(error);
+                    return None;
+                }
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+/// Walks the tree at `root`, lazily yielding each TODO-like comment found in
+/// its source files
+///
+/// Files are read one at a time as the iterator is advanced, so memory use
+/// doesn't grow with the size of the tree.  Problems with individual files or
+/// directories (including [`ParseWarning`]s) are yielded as errors, after
+/// which the walk continues (unless `config.strict_files` says otherwise).
+///
+/// Symlinks to files are scanned like the files themselves.  Symlinks to
+/// directories are only descended into if `config.follow_links` is set.  Then
+/// walkdir reports any loops as errors rather than following them, and each
+/// file or directory is only visited once, however many paths lead to it, so
+/// nothing is scanned twice.  Whichever path the walk reaches first is the
+/// one reported.
+///
+/// The rules for skipping directories and files apply to the paths where
+/// they're found, not to where symlinks lead: a symlink named "vendor" to a
+/// directory named "node_modules" is skipped by `skip_dirs` only if that
+/// includes "vendor".
+pub fn scan<'a>(root: &'a Path, config: &'a Config) -> Scan<'a> {
+    let mut ignores = config.ignore_files.then(|| IgnoreRules::new(root));
+    let mut visited = HashSet::new();
+    // Entries are sorted by name so that the walk (and so the order in which
+    // comments are found) doesn't depend on the order the filesystem lists
+    // them in.
+    let mut walk = walkdir::WalkDir::new(root)
+        .follow_links(config.follow_links)
+        .same_file_system(config.same_file_system)
+        .sort_by_file_name();
+    if let Some(depth) = config.max_depth {
+        // walkdir still yields the entries at the maximum depth (it just
+        // doesn't descend into them), so depths are counted the same way as
+        // in the checks below, and build output directories are still
+        // noticed with a depth of 1.
+        walk = walk.max_depth(depth);
+    }
+    let entries = walk.into_iter().filter_entry(move |e| {
+        // Skip build output directories (like Cargo's "target") found at
+        // the root.
+        let build_dir = if e.depth() == 1 && e.file_type().is_dir() {
+            e.file_name()
+                .to_str()
+                .and_then(|name| config.build_dirs.iter().find(|b| *b == name))
+        } else {
+            None
+        };
+        if let Some(name) = build_dir {
+            if config.progress {
+                eprintln!(
+                    "skipping {:?} (looks like {:?} directory)",
+                    e.path().display(),
+                    name
+                );
+            }
+        }
+        if build_dir.is_some() {
+            return false;
+        }
+
+        // Skip directories with names we've been told to skip anywhere.
+        let skip_dir = if e.depth() > 0 && e.file_type().is_dir() {
+            e.file_name()
+                .to_str()
+                .and_then(|name| config.skip_dirs.iter().find(|d| *d == name))
+        } else {
+            None
+        };
+        if let Some(name) = skip_dir {
+            if config.progress {
+                eprintln!(
+                    "skipping {:?} (directories named {:?} are skipped)",
+                    e.path().display(),
+                    name
+                );
+            }
+            return false;
+        }
+
+        // Skip anything we've been told to exclude.
+        let relative = e.path().strip_prefix(root).unwrap_or(e.path());
+        let excluded =
+            if e.depth() > 0 { config.excluded_by(relative) } else { None };
+        if let Some(glob) = excluded {
+            if config.progress {
+                eprintln!(
+                    "skipping {:?} (excluded by {:?})",
+                    e.path().display(),
+                    glob.as_str()
+                );
+            }
+            return false;
+        }
+
+        // Skip anything that ignore files say to ignore.
+        if e.depth() > 0
+            && ignores.as_mut().is_some_and(|ignores| {
+                ignores.is_ignored(e.path(), e.file_type().is_dir())
+            })
+        {
+            if config.progress {
+                eprintln!("skipping {:?} (ignored)", e.path().display());
+            }
+            return false;
+        }
+
+        // When following symlinks, skip anything we've already visited by
+        // another path.
+        if config.follow_links {
+            if let Ok(real) = std::fs::canonicalize(e.path()) {
+                if !visited.insert(real) {
+                    if config.progress {
+                        eprintln!(
+                            "skipping {:?} (already visited by another \
+                                 path)",
+                            e.path().display()
+                        );
+                    }
+                    return false;
+                }
+            }
+        }
+
+        true
+    });
+
+    Scan {
+        root,
+        config,
+        entries: Box::new(entries),
+        pending: Vec::new().into_iter(),
+        stats: ScanStats::default(),
+        failure: None,
+    }
+}
+
+/// Returns the paths of the files in the tree at `root` that [`scan()`] would
+/// look at, in the order it would look at them, without reading them
+///
+/// Files that the scan would skip because of their size or modification time
+/// (see [`Config::max_filesize`] and [`Config::modified_since`]) are included.
+pub fn source_files(root: &Path, config: &Config) -> Vec<PathBuf> {
+    scan(root, config)
+        .entries
+        .filter_map(Result::ok)
+        .filter(|e| {
+            let given = e.depth() == 0 && e.file_type().is_file();
+            !e.file_type().is_dir() && (given || config.wants_file(e.path()))
+        })
+        .map(walkdir::DirEntry::into_path)
+        .collect()
+}
+
+/// Walks the tree at `root` like [`scan()`], calling `f` with each comment
+/// found and each problem with an individual file or directory
+///
+/// `f` is called once per [`Comment`] (so once per label, for a comment with
+/// several labels), in the order that [`scan()`] would yield them: files in
+/// the order walkdir visits them (sorted by name), and within a file, any
+/// warnings and then comments from top to bottom.  It decides what to do with
+/// each one: count it, print it, collect it (e.g., with
+/// [`CommentTracker::add()`]), or send it elsewhere.
+///
+/// Files are read and parsed on `config.threads` threads, but `f` is always
+/// called on this one, in the order above.
+///
+/// This returns counts of what the scan did, or with `config.strict_files`,
+/// the error that ended the scan early.
+pub fn scan_with<F>(
+    root: &Path,
+    config: &Config,
+    mut f: F,
+) -> Result<ScanStats, anyhow::Error>
+where
+    F: FnMut(Result<Comment, anyhow::Error>),
+{
+    let threads = config.threads.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    });
+    if threads > 1 {
+        return scan_parallel(root, config, threads, f);
+    }
+
+    let mut scan = scan(root, config);
+    scan.by_ref().for_each(&mut f);
+    match scan.failure.take() {
+        Some(error) => Err(error),
+        None => Ok(scan.stats),
+    }
+}
+
+/// Does the work of [`scan_with()`] using `threads` worker threads
+///
+/// This thread walks the whole tree first.  Then the workers read and parse
+/// the files while this thread hands the results to `f` in walk order, so
+/// the order doesn't depend on how the work was scheduled.
+fn scan_parallel<F>(
+    root: &Path,
+    config: &Config,
+    threads: usize,
+    mut f: F,
+) -> Result<ScanStats, anyhow::Error>
+where
+    F: FnMut(Result<Comment, anyhow::Error>),
+{
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::sync::Mutex;
+
+    let mut scan = scan(root, config);
+    let start = Instant::now();
+    let entries: Vec<_> = scan.entries.by_ref().collect();
+    let mut stats =
+        ScanStats { walk_time: start.elapsed(), ..ScanStats::default() };
+    let nworkers = threads.min(entries.len()).max(1);
+    let queue = Mutex::new(entries.into_iter().enumerate());
+    let stop = AtomicBool::new(false);
+    let mut failure = None;
+
+    std::thread::scope(|s| {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let workers: Vec<_> = (0..nworkers)
+            .map(|_| {
+                let tx = tx.clone();
+                let (queue, stop) = (&queue, &stop);
+                s.spawn(move || {
+                    let mut stats = ScanStats::default();
+                    while !stop.load(Ordering::Relaxed) {
+                        let Some((i, entry)) = queue.lock().unwrap().next()
+                        else {
+                            break;
+                        };
+                        let mut warnings = Vec::new();
+                        let result = scan_file(
+                            root,
+                            config,
+                            &mut stats,
+                            entry,
+                            &mut warnings,
+                        );
+                        if tx.send((i, warnings, result)).is_err() {
+                            break;
+                        }
+                    }
+                    stats
+                })
+            })
+            .collect();
+        drop(tx);
+
+        // Results arrive in whatever order the workers finish them.  Hold on
+        // to each one until everything before it has been handed over.
+        let mut done = BTreeMap::new();
+        let mut next = 0;
+        for (i, warnings, result) in rx {
+            done.insert(i, (warnings, result));
+            while let Some((warnings, result)) = done.remove(&next) {
+                next += 1;
+                if failure.is_some() {
+                    continue;
+                }
+                match result {
+                    Ok(comments) => {
+                        warnings.into_iter().map(Err).for_each(&mut f);
+                        comments.into_iter().map(Ok).for_each(&mut f);
+                    }
+                    Err(error) if config.strict_files => {
+                        failure = Some(error);
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                    Err(error) => f(Err(error)),
+                }
+            }
+        }
+
+        for worker in workers {
+            stats += worker.join().expect("scan worker panicked");
+        }
+    });
+
+    match failure {
+        Some(error) => Err(error),
+        None => Ok(stats),
+    }
+}
+
+/// Returns the TODO-like comments in the source files of commit (or other
+/// tree-ish) `rev` in the git repository at `repo`, without checking it out
+///
+/// Paths are relative to the top of the repository, which plays the part of
+/// the root of the tree for `config`.  Files that aren't valid UTF-8 are
+/// skipped.  Warnings from parsing the files are printed to stderr, except
+/// that with `config.strict_comments`, a malformed block comment is an error.
+pub fn scan_git_ref(
+    repo: &Path,
+    rev: &str,
+    config: &Config,
+) -> Result<Vec<Comment>, anyhow::Error> {
+    let files = git::read_tree(repo, rev, |path| wants_repo_path(config, path))
+        .with_context(|| format!("reading files at {:?}", rev))?;
+
+    let mut comments = Vec::new();
+    for (path, contents) in files {
+        let description = format!("{:?} at {:?}", path, rev);
+        comments.extend(blob_comments(&path, contents, &description, config)?);
+    }
+
+    Ok(comments)
+}
+
+/// Returns the TODO-like comments in the git staging area of the repository
+/// containing `repo` that are on lines touched by the staged changes
+///
+/// Comments are found in the staged contents of each changed file, so
+/// unstaged edits to the same file don't count.  Paths are relative to the
+/// top of the repository, and warnings are handled, as with
+/// [`scan_git_ref()`].
+pub fn scan_staged(
+    repo: &Path,
+    config: &Config,
+) -> Result<Vec<Comment>, anyhow::Error> {
+    let files = git::read_staged(repo, |path| wants_repo_path(config, path))
+        .context("reading staged changes")?;
+
+    let mut comments = Vec::new();
+    for file in files {
+        let description = format!("staged {:?}", file.path);
+        comments.extend(
+            blob_comments(&file.path, file.contents, &description, config)?
+                .into_iter()
+                .filter(|c| {
+                    file.changed
+                        .iter()
+                        .any(|&(s, e)| s <= c.end_line && c.line <= e)
+                }),
+        );
+    }
+
+    Ok(comments)
+}
+
+/// Returns whether to scan the file at `path`, relative to the top of a git
+/// repository that plays the part of the root of the tree for `config`
+fn wants_repo_path(config: &Config, path: &str) -> bool {
+    let path = Path::new(path);
+    let mut components = path.components();
+    let top = components.next().map(|c| c.as_os_str());
+    let in_build_dir = components.next().is_some()
+        && top.is_some_and(|top| config.build_dirs.iter().any(|b| top == &**b));
+    let too_deep =
+        config.max_depth.is_some_and(|d| path.components().count() > d);
+    let in_skipped_dir = path.parent().is_some_and(|dir| {
+        dir.components()
+            .any(|c| config.skip_dirs.iter().any(|d| c.as_os_str() == &**d))
+    });
+    config.wants_file(path)
+        && !in_build_dir
+        && !too_deep
+        && !in_skipped_dir
+        && !path
+            .ancestors()
+            .any(|p| !p.as_os_str().is_empty() && config.is_excluded(p))
+}
+
+/// Returns the TODO-like comments in `contents`, the contents of the file at
+/// `path` read from git (described in messages as `description`)
+///
+/// With `config.strict_comments`, this fails if the file has a malformed
+/// block comment.
+fn blob_comments(
+    path: &str,
+    contents: Vec<u8>,
+    description: &str,
+    config: &Config,
+) -> Result<Vec<Comment>, anyhow::Error> {
+    let Ok(contents) = String::from_utf8(contents) else {
+        if config.progress {
+            eprintln!("skipping {} (not UTF-8)", description);
+        }
+        return Ok(Vec::new());
+    };
+    let path = Path::new(path);
+    let keywords = config.keywords.for_path(path);
+    let mut warnings = Vec::new();
+    let comments =
+        find_comments(&contents, path, keywords, config, &mut warnings);
+    for warning in warnings {
+        if warning.is::<MalformedComment>() {
+            return Err(warning);
+        }
+        eprintln!("warn: {:#}", warning);
+    }
+    Ok(comments)
+}
+
+/// Process one file, finding all TODO-like comments
+fn scan_file(
+    root: &Path,
+    config: &Config,
+    stats: &mut ScanStats,
+    maybe_entry: Result<walkdir::DirEntry, walkdir::Error>,
+    warnings: &mut Vec<anyhow::Error>,
+) -> Result<Vec<Comment>, anyhow::Error> {
+    // walkdir may have failed to read this entry.
+    let entry = maybe_entry.context("walking tree")?;
+    let path = entry.path();
+
+    // Skip anything that doesn't end with ".rs" (or ".md", if we're looking
+    // at Markdown), unless it's a file we were given directly.
+    let given = entry.depth() == 0 && entry.file_type().is_file();
+    if !given && !config.wants_file(path) {
+        return Ok(Vec::new());
+    }
+
+    let start = Instant::now();
+    let contents = read_file(config, stats, path);
+    stats.read_time += start.elapsed();
+    let Some(contents) = contents? else {
+        return Ok(Vec::new());
+    };
+
+    // Pull the TODO-like comments out of the file.
+    let start = Instant::now();
+    let relative =
+        if given { path } else { path.strip_prefix(root).unwrap_or(path) };
+    let keywords = config.keywords.for_path(relative);
+    let comments = find_comments(&contents, path, keywords, config, warnings);
+    stats.parse_time += start.elapsed();
+
+    Ok(comments)
+}
+
+/// Returns the TODO-like comments in the Rust source `contents`, using the
+/// default [`Config`]
+///
+/// This is the simplest way to find comments in a string (e.g., in a test).
+/// It's [`scan_string()`] with no file name: the comments' `file` is empty,
+/// and problems parsing the source are ignored.
+pub fn scan_str(contents: &str) -> impl Iterator<Item = Comment> {
+    scan_string(contents, "", &Config::default()).into_iter()
+}
+
+/// Returns the TODO-like comments in `contents`, the source of the file at
+/// `path` (in the language its extension implies, or else `config.lang`)
+///
+/// This is what [`scan()`] does with each file it reads, for callers that have
+/// source from somewhere other than a file (e.g., an editor buffer).  Only
+/// the options in `config` that affect parsing are used.  Any problems
+/// parsing the source are appended to `warnings`.
+///
+/// With `config.scan_markdown`, if `path` is a Markdown file, this returns
+/// its unchecked checklist items that have TODO-like labels instead.
+pub fn find_comments(
+    contents: &str,
+    path: &Path,
+    keywords: &[String],
+    config: &Config,
+    warnings: &mut Vec<anyhow::Error>,
+) -> Vec<Comment> {
+    // Some editors (mostly on Windows) start files with a byte-order mark.
+    // It's not whitespace, so left in, it would hide a comment on the first
+    // line.
+    let contents = match contents.strip_prefix('\u{feff}') {
+        Some(rest) => {
+            if config.progress {
+                eprintln!("ignoring byte-order mark in {:?}", path.display());
+            }
+            rest
+        }
+        None => contents,
+    };
+
+    if config.scan_markdown && is_markdown(path) {
+        let mut items: Vec<Comment> = markdown::checklist_items(contents)
+            .into_iter()
+            .flat_map(|item| {
+                find_kinds_matching(
+                    &item.contents,
+                    keywords,
+                    config.ignore_case,
+                )
+                .into_iter()
+                .map(|k| Comment {
+                    id: stable_id(&k, &item.contents, None),
+                    occurrences: label_positions(
+                        &item.contents,
+                        item.line,
+                        &[item.indent],
+                        &k,
+                        config.ignore_case,
+                    ),
+                    contents: format!("{}\n", item.contents),
+                    file: path.display().to_string(),
+                    line: item.line,
+                    end_line: item.line,
+                    issue: issue_reference(&k),
+                    year: year_tag(&k),
+                    assignee: assignee_tag(&k),
+                    label: k.into_owned(),
+                    item: None,
+                    tag: None,
+                    style: CommentStyle::Markdown,
+                    enclosing: None,
+                    attached: false,
+                    column: None,
+                    context_before: Vec::new(),
+                    context_after: Vec::new(),
+                    blame: None,
+                })
+                .collect::<Vec<_>>()
+            })
+            .collect();
+        add_context(&mut items, contents, config.context_lines);
+        return items;
+    }
+
+    let syntax =
+        lang::syntax_for_path(path).or(config.lang).unwrap_or(Syntax::RUST);
+    let mut iter = CommentIterator::new(contents)
+        .syntax(syntax)
+        .split_on_dedent(config.split_on_dedent)
+        .preserve_indent(config.preserve_indent);
+    let mut comments: Vec<Comment> = iter
+        .by_ref()
+        .flat_map(|raw| {
+            found_possible_comment(
+                raw,
+                path,
+                keywords,
+                &config.patterns,
+                config.ignore_case,
+            )
+        })
+        .collect();
+    if config.strict_comments && !iter.warnings().is_empty() {
+        // What we found in the file can't be trusted, so report none of it.
+        warnings.extend(iter.warnings().iter().map(|w| {
+            anyhow::Error::new(MalformedComment {
+                file: path.display().to_string(),
+                line: w.line,
+                message: w.message.clone(),
+            })
+        }));
+        return Vec::new();
+    }
+    warnings.extend(iter.warnings().iter().map(|w| {
+        anyhow::anyhow!("{}:{}: {}", path.display(), w.line, w.message)
+    }));
+
+    if config.scan_macros && syntax.rust {
+        comments.extend(find_macro_calls(contents).into_iter().map(|call| {
+            Comment {
+                id: stable_id(&call.name, &call.text, None),
+                end_line: call.line + call.text.lines().count().max(1) - 1,
+                contents: call.text,
+                file: path.display().to_string(),
+                line: call.line,
+                label: call.name,
+                issue: None,
+                year: None,
+                assignee: None,
+                item: None,
+                tag: None,
+                style: CommentStyle::Macro { message: call.message },
+                enclosing: None,
+                attached: true,
+                occurrences: vec![(call.line, call.column)],
+                column: None,
+                context_before: Vec::new(),
+                context_after: Vec::new(),
+                blame: None,
+            }
+        }));
+        comments.sort_by_key(|c| c.line);
+    }
+
+    add_context(&mut comments, contents, config.context_lines);
+    comments
+}
+
+/// Fills in the `n` lines of `contents` before and after each of `comments`
+/// (fewer near the start or end of the file)
+fn add_context(comments: &mut [Comment], contents: &str, n: usize) {
+    if n == 0 {
+        return;
+    }
+    let lines = source_lines(contents);
+    let slice = |range: std::ops::Range<usize>| -> Vec<String> {
+        let end = range.end.min(lines.len());
+        let start = range.start.min(end);
+        lines[start..end].iter().map(|l| l.trim_end().to_string()).collect()
+    };
+    for c in comments {
+        // `line` starts from 1, so the comment's first line is at index
+        // `line - 1`.
+        let first = c.line - 1;
+        let last = c.end_line;
+        c.context_before = slice(first.saturating_sub(n)..first);
+        c.context_after = slice(last..last + n);
+    }
+}
+
+/// Returns the TODO-like comments in `src`, as though it were the contents of
+/// a file called `filename`
+///
+/// This is a shorthand for [`find_comments()`] (using the keywords that
+/// `config` has for `filename`) for one-off checks, like trying out a
+/// configuration.  Any warnings from parsing `src` are discarded.
+pub fn scan_string(src: &str, filename: &str, config: &Config) -> Vec<Comment> {
+    let path = Path::new(filename);
+    let keywords = config.keywords.for_path(path);
+    find_comments(src, path, keywords, config, &mut Vec::new())
+}
+
+/// Reads the file at `path`, returning `None` if it should be skipped
+fn read_file(
+    config: &Config,
+    stats: &mut ScanStats,
+    path: &Path,
+) -> Result<Option<String>, anyhow::Error> {
+    // Open the file and then stat it (presumably by fd).  Opening follows a
+    // symlink, so this looks at whatever the link points to.  Skip anything
+    // that's not a regular file, including symlinks to directories that we
+    // weren't asked to follow.
+    let file = std::fs::File::open(path).with_context(|| {
+        if path.is_symlink() && !path.exists() {
+            format!("open {:?} (dangling symlink)", path.display())
+        } else {
+            format!("open {:?}", path.display())
+        }
+    })?;
+    let metadata = file
+        .metadata()
+        .with_context(|| format!("metadata for {:?}", path.display()))?;
+    if !metadata.is_file() {
+        return Ok(None);
+    }
+
+    // Skip files that haven't changed recently enough, if we've been asked
+    // to.  If the platform can't tell us the mtime, scan the file anyway.
+    if let Some(since) = config.modified_since {
+        if metadata.modified().is_ok_and(|mtime| mtime < since) {
+            stats.files_unmodified += 1;
+            return Ok(None);
+        }
+    }
+
+    // Skip files that are too small or too big, if we've been asked to.
+    let size = metadata.len();
+    if config.min_filesize.is_some_and(|min| size < min)
+        || config.max_filesize.is_some_and(|max| size > max)
+    {
+        if config.progress {
+            eprintln!(
+                "skipping {:?} (size {} is outside the allowed range)",
+                path.display(),
+                size
+            );
+        }
+        stats.files_wrong_size += 1;
+        return Ok(None);
+    }
+
+    // Read the file.
+    if config.progress {
+        eprintln!("reading {:?}", path.display());
+    }
+    let contents = std::io::read_to_string(&file)
+        .with_context(|| format!("read {:?}", path.display()))?;
+    stats.files_scanned += 1;
+    Ok(Some(contents))
+}
+
+/// Returns a `Comment` for each TODO-like label found in the comment `raw`
+/// from file `path`
+fn found_possible_comment(
+    raw: RawComment,
+    path: &Path,
+    keywords: &[String],
+    patterns: &[Regex],
+    ignore_case: bool,
+) -> Vec<Comment> {
+    // Figure out what "kinds" of comment this is.  If there are none, we
+    // won't report it at all.  Otherwise, we will report the entire comment
+    // once for each "kind" that we find in it.
+    let mut kinds: BTreeMap<Cow<str>, Vec<(usize, usize)>> =
+        find_kinds_matching(&raw.contents, keywords, ignore_case)
+            .into_iter()
+            .map(|k| {
+                let occurrences = label_positions(
+                    &raw.contents,
+                    raw.line,
+                    &raw.indents,
+                    &k,
+                    ignore_case,
+                );
+                (k, occurrences)
+            })
+            .collect();
+    for (label, position) in
+        pattern_labels(&raw.contents, raw.line, &raw.indents, patterns)
+    {
+        let occurrences = kinds.entry(Cow::Owned(label)).or_default();
+        if !occurrences.contains(&position) {
+            occurrences.push(position);
+            occurrences.sort();
+        }
+    }
+
+    kinds
+        .into_iter()
+        .map(|(k, occurrences)| {
+            let kind = comment_kind(
+                &raw.contents,
+                occurrences.iter().map(|(line, _)| line - raw.line),
+            );
+            (k, occurrences, kind)
+        })
+        .map(|(k, occurrences, kind)| Comment {
+            id: stable_id(&k, &raw.contents, raw.enclosing.as_deref()),
+            occurrences,
+            contents: raw.contents.clone(),
+            file: path.display().to_string(),
+            line: raw.line,
+            end_line: raw.end_line,
+            issue: issue_reference(&k),
+            year: year_tag(&k),
+            assignee: assignee_tag(&k),
+            label: k.into_owned(),
+            item: raw.item.clone(),
+            tag: None,
+            style: CommentStyle::Comment(kind),
+            enclosing: raw.enclosing.clone(),
+            attached: raw.attached,
+            column: None,
+            context_before: Vec::new(),
+            context_after: Vec::new(),
+            blame: None,
+        })
+        .collect()
+}
+
+/// Returns the label and position (line and column) of each match of
+/// `patterns` in `text`, which starts at line `line` with the lines indented
+/// by `indents`
+///
+/// The label is the text matched by the group named "label", if the pattern
+/// has one and it took part in the match, or else the whole match, with runs
+/// of whitespace (including line breaks) turned into single spaces.  Matches
+/// that are only whitespace are skipped.
+fn pattern_labels(
+    text: &str,
+    line: usize,
+    indents: &[usize],
+    patterns: &[Regex],
+) -> Vec<(String, (usize, usize))> {
+    let mut labels = Vec::new();
+    for pattern in patterns {
+        let group = pattern.group_index("label");
+        for captures in pattern.captures_all(text) {
+            let Some(range) = group
+                .and_then(|i| captures[i].clone())
+                .or_else(|| captures[0].clone())
+            else {
+                continue;
+            };
+            let matched = &text[range.clone()];
+            let label =
+                matched.split_whitespace().collect::<Vec<_>>().join(" ");
+            if label.is_empty() {
+                continue;
+            }
+
+            let offset =
+                range.start + (matched.len() - matched.trim_start().len());
+            let before = &text[..offset];
+            let i = before.matches('\n').count();
+            let line_start = before.rfind('\n').map_or(0, |n| n + 1);
+            let indent = indents.get(i).copied().unwrap_or(0);
+            let column = indent + before[line_start..].chars().count() + 1;
+            labels.push((label, (line + i, column)));
+        }
+    }
+    labels
+}
+
+/// Classifies the comment whose text is `contents`, given the lines of it
+/// (counting from 0) that contain the label being reported
+///
+/// A run of line comments can mix doc comment lines with others, so it's a
+/// doc comment if any of the lines with the label are, or (if the label is
+/// a phrase split across lines) if its first line is.  An empty block comment
+/// (`/**/`) and ones starting with more than two asterisks (`/***`) aren't
+/// doc comments, just as for rustdoc, and neither are lines starting with
+/// more than three slashes (`////`).
+fn comment_kind(
+    contents: &str,
+    label_lines: impl IntoIterator<Item = usize>,
+) -> CommentKind {
+    let lines: Vec<&str> = contents.lines().map(str::trim_start).collect();
+    let first = lines.first().copied().unwrap_or("");
+    if first.starts_with("/*") {
+        let doc = first.starts_with("/*!")
+            || (first.starts_with("/**")
+                && !first.starts_with("/**/")
+                && !first.starts_with("/***"));
+        return if doc { CommentKind::DocBlock } else { CommentKind::Block };
+    }
+
+    let is_doc = |line: &str| {
+        line.starts_with("//!")
+            || (line.starts_with("///") && !line.starts_with("////"))
+    };
+    let mut label_lines = label_lines.into_iter().peekable();
+    let doc = if label_lines.peek().is_some() {
+        label_lines.any(|i| lines.get(i).is_some_and(|line| is_doc(line)))
+    } else {
+        is_doc(first)
+    };
+    if doc {
+        CommentKind::DocLine
+    } else {
+        CommentKind::Line
+    }
+}
+
+/// Returns the distinct "kinds" of TODO-like comment (labels) found in `text`
+///
+/// There may be any number of these.  A comment might have no TODO-like things
+/// in it or one of them, or more than one (e.g., "TODO-security" and
+/// "TODO-coverage").
+///
+/// A keyword containing spaces (e.g., "KNOWN ISSUE") is a phrase.  It matches
+/// that sequence of whole words anywhere in the comment, even across line
+/// breaks, and the label is the phrase itself.
+pub fn find_kinds<'a>(
+    text: &'a str,
+    keywords: &[String],
+) -> BTreeSet<Cow<'a, str>> {
+    find_kinds_matching(text, keywords, false)
+}
+
+/// Like [`find_kinds()`], but with `ignore_case`, keywords match regardless of
+/// (ASCII) case, and the part of each label that matched a keyword is
+/// uppercased so that case variants come out the same (e.g., "todo:" and
+/// "Todo" are both labeled "TODO")
+fn find_kinds_matching<'a>(
+    text: &'a str,
+    keywords: &[String],
+    ignore_case: bool,
+) -> BTreeSet<Cow<'a, str>> {
+    let mut found_kinds = BTreeSet::new();
+    for word in text.split_whitespace() {
+        if ignore_case {
+            let label = word.strip_suffix(':').unwrap_or(word);
+            let keyword = keywords.iter().find(|k| {
+                label.get(..k.len()).is_some_and(|p| p.eq_ignore_ascii_case(k))
+            });
+            if let Some(k) = keyword {
+                let canonical =
+                    format!("{}{}", k.to_ascii_uppercase(), &label[k.len()..]);
+                found_kinds.insert(if canonical == label {
+                    Cow::Borrowed(label)
+                } else {
+                    Cow::Owned(canonical)
+                });
+            }
+        } else if keywords.iter().any(|k| word.starts_with(k.as_str())) {
+            let mut label = word;
+            // People use "TODO" and "TODO:" interchangeably.  Treat them
+            // the same.
+            if word.ends_with(':') {
+                label = &word[0..word.len() - 1];
+            }
+            found_kinds.insert(Cow::Borrowed(label));
+        }
+    }
+
+    let phrases: Vec<Vec<&str>> = keywords
+        .iter()
+        .filter(|k| k.contains(char::is_whitespace))
+        .map(|k| k.split_whitespace().collect())
+        .collect();
+    if !phrases.is_empty() {
+        // Compare phrases against the words of the comment with comment
+        // markers and trailing punctuation removed, so that a phrase can
+        // continue onto the next line of a comment.
+        let words: Vec<&str> = text
+            .split_whitespace()
+            .filter(|w| !is_comment_marker(w))
+            .map(|w| w.trim_end_matches([':', ',', '.', ';', '!', '?']))
+            .collect();
+        for phrase in phrases {
+            if words
+                .windows(phrase.len())
+                .any(|window| same_words(window, &phrase, ignore_case))
+            {
+                let label = phrase.join(" ");
+                found_kinds.insert(Cow::Owned(if ignore_case {
+                    label.to_ascii_uppercase()
+                } else {
+                    label
+                }));
+            }
+        }
+    }
+
+    found_kinds
+}
+
+/// Returns the line and column (both starting from 1) of each place that
+/// `label` (as found by [`find_kinds()`]) appears in `text`
+///
+/// `text` starts on line `line` of the source, and `indents` says how many
+/// characters of indentation preceded each of its lines there.
+///
+/// Columns count characters, not bytes or display width.  In particular, a
+/// tab is one column, not a jump to the next tab stop: that's what rustc's
+/// diagnostics and editors' "go to line:column" commands expect.
+///
+/// With `ignore_case`, `label` matches regardless of (ASCII) case, as in
+/// [`find_kinds_matching()`].
+fn label_positions(
+    text: &str,
+    line: usize,
+    indents: &[usize],
+    label: &str,
+    ignore_case: bool,
+) -> Vec<(usize, usize)> {
+    let words = text.lines().enumerate().flat_map(|(i, text_line)| {
+        let indent = indents.get(i).copied().unwrap_or(0);
+        text_line.split_whitespace().map(move |word| {
+            let offset = word.as_ptr() as usize - text_line.as_ptr() as usize;
+            let column = indent + text_line[..offset].chars().count() + 1;
+            (line + i, column, word)
+        })
+    });
+
+    if !label.contains(char::is_whitespace) {
+        return words
+            .filter(|(_, _, w)| {
+                let w = w.strip_suffix(':').unwrap_or(w);
+                same_words(&[w], &[label], ignore_case)
+            })
+            .map(|(line, column, _)| (line, column))
+            .collect();
+    }
+
+    // As in find_kinds(), a phrase is matched against the words with comment
+    // markers and trailing punctuation removed.
+    let phrase: Vec<&str> = label.split_whitespace().collect();
+    let words: Vec<_> = words
+        .filter(|(_, _, w)| !is_comment_marker(w))
+        .map(|(line, column, w)| {
+            (line, column, w.trim_end_matches([':', ',', '.', ';', '!', '?']))
+        })
+        .collect();
+    words
+        .windows(phrase.len())
+        .filter(|window| {
+            let window: Vec<&str> = window.iter().map(|(_, _, w)| *w).collect();
+            same_words(&window, &phrase, ignore_case)
+        })
+        .map(|window| (window[0].0, window[0].1))
+        .collect()
+}
+
+/// Returns whether the words `a` are the same as the words `b`, ignoring
+/// (ASCII) case if `ignore_case` is set
+fn same_words(a: &[&str], b: &[&str], ignore_case: bool) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| {
+            if ignore_case {
+                a.eq_ignore_ascii_case(b)
+            } else {
+                a == b
+            }
+        })
+}
+
+/// Returns whether `word` consists only of comment markers (like "//" or
+/// "*", as at the start of a line in a block comment)
+fn is_comment_marker(word: &str) -> bool {
+    ["///", "//!", "//", "/**", "/*!", "/*", "*/", "*", "#"].contains(&word)
+}
+
+/// Returns an identifier for a comment with label `label` and text `contents`
+/// inside item `enclosing` that stays the same from run to run
+///
+/// This is a hash (64-bit FNV-1a, as 16 hex digits) of the label, the text with
+/// whitespace normalized, and the name of the enclosing item.  The line number
+/// and file name are deliberately left out so that the ID survives the
+/// comment being moved.  Identical comments in the same item share an ID.
+pub fn stable_id(
+    label: &str,
+    contents: &str,
+    enclosing: Option<&str>,
+) -> String {
+    let mut hash = Fnv1a::new();
+    hash.add(label.as_bytes());
+    hash.add(&[0]);
+    for word in contents.split_whitespace() {
+        hash.add(word.as_bytes());
+        hash.add(b" ");
+    }
+    hash.add(&[0]);
+    hash.add(enclosing.unwrap_or("").as_bytes());
+    hash.hex()
+}
+
+/// 64-bit FNV-1a hash, for [`stable_id()`] and [`Comment::content_hash()`]
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf29ce484222325)
+    }
+
+    fn add(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= u64::from(*b);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// Returns the hash as 16 hex digits
+    fn hex(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+/// Returns the issue number referenced by a label like "TODO#123" or
+/// "TODO(#123)", if any
+fn issue_reference(label: &str) -> Option<u64> {
+    let (_, rest) = label.split_once('#')?;
+    let digits = rest.split(|c: char| !c.is_ascii_digit()).next().unwrap();
+    digits.parse().ok()
+}
+
+/// Returns the name given in parentheses in a label like "TODO(alice)", if
+/// any
+///
+/// A year ("TODO(2025)") or an issue ("TODO(#123)") isn't a name.  A name can
+/// come with an issue, as in "TODO(alice)#123" or "TODO(alice,#123)".
+fn assignee_tag(label: &str) -> Option<String> {
+    let (_, rest) = label.split_once('(')?;
+    let (inside, _) = rest.split_once(')')?;
+    if year_tag(label).is_some() {
+        return None;
+    }
+    let name = inside
+        .split(',')
+        .map(str::trim)
+        .find(|part| !part.is_empty() && !part.starts_with('#'))?;
+    Some(name.to_string())
+}
+
+/// Returns the year given in parentheses in a label like "TODO(2025)", if
+/// any
+///
+/// Other things in parentheses, like a name ("TODO(alice)") or an issue
+/// ("TODO(#123)"), aren't years.
+fn year_tag(label: &str) -> Option<u32> {
+    let (_, rest) = label.split_once('(')?;
+    let year = rest.strip_suffix(')')?;
+    if year.len() != 4 || !year.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    year.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the comments found in `src`, as the contents of a Rust file,
+    /// with the default configuration
+    fn comments_in(src: &str) -> Vec<Comment> {
+        scan_string(src, "src/lib.rs", &Config::default())
+    }
+
+    #[test]
+    fn first_line_skips_bare_block_opener() {
+        let comments = comments_in("/*\n * TODO: fix this\n * soon\n */\n");
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].first_line(), "TODO: fix this");
+
+        let comments = comments_in("/**\n\n   TODO: blank lines too\n*/\n");
+        assert_eq!(comments[0].first_line(), "TODO: blank lines too");
+    }
+
+    #[test]
+    fn scan_str_is_scan_string_without_a_file() {
+        let src = "// TODO: one\nfn f() {} // XXX two\n";
+        let comments: Vec<Comment> = scan_str(src).collect();
+        assert_eq!(labels(&comments), ["TODO", "XXX"]);
+        assert!(comments.iter().all(|c| c.file.is_empty()));
+        let contents = |comments: &[Comment]| {
+            comments.iter().map(|c| c.contents.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            contents(&comments),
+            contents(&scan_string(src, "", &Config::default()))
+        );
+    }
+
+    #[test]
+    fn first_line_strips_markers() {
+        let comments = comments_in("// TODO: a line comment\n// more\n");
+        assert_eq!(comments[0].first_line(), "TODO: a line comment");
+        let comments = comments_in("/* TODO: one line */\n");
+        assert_eq!(comments[0].first_line(), "TODO: one line");
+        let comments = comments_in("fn f() {} // XXX: after code\n");
+        assert_eq!(comments[0].first_line(), "XXX: after code");
+    }
+
+    /// A directory for a test to build a tree in, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!(
+                "todos-test-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        /// Writes `contents` to the file at `path` within the directory,
+        /// creating its parent directories as needed
+        fn write(&self, path: &str, contents: &str) {
+            let path = self.0.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Returns the files (relative to `root`) in which [`scan()`] finds
+    /// comments, in order
+    fn files_scanned(root: &Path, config: &Config) -> Vec<String> {
+        let mut files: Vec<String> = scan(root, config)
+            .map(|c| c.unwrap().file)
+            .map(|f| {
+                let f = Path::new(&f).strip_prefix(root).unwrap().to_owned();
+                f.display().to_string()
+            })
+            .collect();
+        files.dedup();
+        files
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_files_are_scanned() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new("symlinks");
+        dir.write("elsewhere/file.rs", "// TODO in a linked file\n");
+        dir.write("elsewhere/dir/inner.rs", "// TODO in a linked dir\n");
+        dir.write("tree/real.rs", "// TODO in a real file\n");
+        let tree = dir.0.join("tree");
+        symlink(dir.0.join("elsewhere/file.rs"), tree.join("link.rs")).unwrap();
+        symlink(dir.0.join("elsewhere/dir"), tree.join("linkdir")).unwrap();
+
+        let mut config = Config::default();
+        assert_eq!(files_scanned(&tree, &config), ["link.rs", "real.rs"]);
+
+        config.follow_links = true;
+        assert_eq!(
+            files_scanned(&tree, &config),
+            ["link.rs", "linkdir/inner.rs", "real.rs"]
+        );
+    }
+
+    #[test]
+    fn skip_dirs_apply_at_any_depth() {
+        let dir = TempDir::new("skip-dirs");
+        for file in [
+            "target/root.rs",
+            "crates/foo/target/nested.rs",
+            "crates/foo/src/target.rs",
+            "node_modules/dep.rs",
+        ] {
+            dir.write(file, "// TODO\n");
+        }
+
+        let mut config = Config::default();
+        let default = ["crates/foo/src/target.rs", "node_modules/dep.rs"];
+        assert_eq!(files_scanned(&dir.0, &config), default);
+
+        // Replacing the build directories leaves "target" in the skip list.
+        config.build_dirs = vec![String::from("build")];
+        assert_eq!(files_scanned(&dir.0, &config), default);
+
+        // Replacing the skip list leaves "target" in the build directories,
+        // which only apply at the root.
+        config.build_dirs =
+            DEFAULT_BUILD_DIRS.iter().map(|d| d.to_string()).collect();
+        config.skip_dirs = vec![String::from("node_modules")];
+        assert_eq!(
+            files_scanned(&dir.0, &config),
+            ["crates/foo/src/target.rs", "crates/foo/target/nested.rs"]
+        );
+
+        config.build_dirs = Vec::new();
+        assert_eq!(
+            files_scanned(&dir.0, &config),
+            [
+                "crates/foo/src/target.rs",
+                "crates/foo/target/nested.rs",
+                "target/root.rs"
+            ]
+        );
+    }
+
+    /// Returns the labels of `comments`, in order
+    fn labels(comments: &[Comment]) -> Vec<&str> {
+        comments.iter().map(|c| c.label.as_str()).collect()
+    }
+
+    #[test]
+    fn phrase_keywords_match_whole_words() {
+        let keywords = vec![String::from("KNOWN ISSUE"), String::from("TODO")];
+        let kinds = |text| {
+            find_kinds(text, &keywords)
+                .into_iter()
+                .map(Cow::into_owned)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kinds("// KNOWN ISSUE: it leaks"), ["KNOWN ISSUE"]);
+        assert_eq!(kinds("// a KNOWN ISSUE, sadly"), ["KNOWN ISSUE"]);
+        assert_eq!(
+            kinds("// TODO fix the KNOWN ISSUE"),
+            ["KNOWN ISSUE", "TODO"]
+        );
+        assert!(kinds("// KNOWN ISSUES").is_empty());
+        assert!(kinds("// UNKNOWN ISSUE").is_empty());
+        assert!(kinds("// known issue").is_empty());
+    }
+
+    #[test]
+    fn phrase_keywords_match_across_lines() {
+        let mut config = Config::default();
+        config.keywords.default = vec![String::from("NEEDS REVIEW")];
+        let comments = scan_string(
+            "// This NEEDS\n// REVIEW before release.\nfn f() {}\n",
+            "src/lib.rs",
+            &config,
+        );
+        assert_eq!(labels(&comments), ["NEEDS REVIEW"]);
+        assert_eq!((comments[0].line, comments[0].end_line), (1, 2));
+
+        let comments = scan_string(
+            "/*\n * NEEDS\n * REVIEW\n */\n",
+            "src/lib.rs",
+            &config,
+        );
+        assert_eq!(labels(&comments), ["NEEDS REVIEW"]);
+    }
+
+    #[test]
+    fn ignore_case_merges_case_variants() {
+        let src = "// fixme: one\nfn a() {}\n// FixMe two\nfn b() {}\n\
+                   // FIXME three\nfn c() {}\n// FIXME-perf: four\n";
+
+        // By default, only the exact case matches.
+        assert_eq!(labels(&comments_in(src)), ["FIXME", "FIXME-perf"]);
+
+        let config = Config { ignore_case: true, ..Config::default() };
+        let comments = scan_string(src, "src/lib.rs", &config);
+        assert_eq!(
+            labels(&comments),
+            ["FIXME", "FIXME", "FIXME", "FIXME-perf"]
+        );
+
+        let mut tracker = CommentTracker::new();
+        comments.into_iter().for_each(|c| tracker.add(c));
+        let groups: Vec<_> = tracker.comments_by_kind.keys().collect();
+        assert_eq!(groups, ["FIXME", "FIXME-perf"]);
+        assert_eq!(tracker.count("FIXME"), 3);
+    }
+
+    #[test]
+    fn total_counts_comments_with_several_labels_once() {
+        let mut tracker = CommentTracker::new();
+        let src = "// TODO-security TODO-coverage check the input\n";
+        for c in comments_in(src) {
+            tracker.add(c);
+        }
+        assert_eq!(tracker.count("TODO-security"), 1);
+        assert_eq!(tracker.count("TODO-coverage"), 1);
+        assert_eq!(tracker.total(), 1);
+
+        // A different comment with one of the same labels counts separately.
+        for c in comments_in("fn f() {}\n// TODO-security and another\n") {
+            tracker.add(c);
+        }
+        assert_eq!(tracker.count("TODO-security"), 2);
+        assert_eq!(tracker.count("TODO-coverage"), 1);
+        assert_eq!(tracker.total(), 2);
+        assert_eq!(tracker.counts_by_file()["src/lib.rs"], 2);
+    }
+
+    #[test]
+    fn crlf_finds_the_same_comments() {
+        let lf = "// TODO: first\n/*\n * FIXME: second\n */\nfn f() {} // XXX";
+        let summary = |src: &str| {
+            comments_in(src)
+                .into_iter()
+                .map(|c| {
+                    (c.label.clone(), c.position(), c.id.clone(), c.contents)
+                })
+                .collect::<Vec<_>>()
+        };
+        let expected = summary(lf);
+        assert_eq!(expected.len(), 3);
+        assert_eq!(summary(&lf.replace('\n', "\r\n")), expected);
+        assert_eq!(
+            summary(&format!("{}\r\n", lf.replace('\n', "\r\n"))),
+            expected
+        );
+    }
+
+    #[test]
+    fn byte_order_mark_is_ignored() {
+        let src = "\u{feff}// TODO: on the first line\nfn f() {} // XXX\n";
+        let comments = comments_in(src);
+        assert_eq!(labels(&comments), ["TODO", "XXX"]);
+        assert_eq!(comments[0].first_line(), "TODO: on the first line");
+        assert_eq!(comments[0].position(), (1, 4));
+        assert_eq!(comments[1].position(), (2, 14));
+
+        // The same goes for a file read from disk.
+        let dir = TempDir::new("bom");
+        dir.write("bom.rs", src);
+        let found: Vec<Comment> =
+            scan(&dir.0, &Config::default()).map(Result::unwrap).collect();
+        assert_eq!(labels(&found), ["TODO", "XXX"]);
+        assert_eq!(found[0].line, 1);
+    }
+
+    #[test]
+    fn default_skips_target_at_any_depth() {
+        let config = Config::default();
+        assert!(!wants_repo_path(&config, "target/debug/build.rs"));
+        assert!(!wants_repo_path(&config, "crates/foo/target/x.rs"));
+        assert!(wants_repo_path(&config, "crates/foo/src/target.rs"));
+        assert!(wants_repo_path(&config, "crates/foo/src/lib.rs"));
+    }
+}
@@ -4,300 +4,4007 @@
 
 //! Simplistic command-line tool to summarize TODO-like comments
 
-use anyhow::ensure;
+use allow::Allowlist;
+use anyhow::anyhow;
+use anyhow::bail;
 use anyhow::Context;
+use baseline::Baseline;
+use config_file::ConfigFile;
+use issues::IssueChecker;
+use issues::IssueState;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::cmp::Reverse;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::Component;
 use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use tags::TagMap;
+use todos::glob;
+use todos::lang;
+use todos::Comment;
+use todos::CommentStyle;
+use todos::CommentTracker;
+use todos::Config;
+use todos::Glob;
+use todos::Item;
+use todos::MalformedComment;
+use todos::Regex;
+use todos::ReportHeader;
+use todos::ScanStats;
 
-fn main() -> Result<(), anyhow::Error> {
-    let argv = std::env::args_os().collect::<Vec<_>>();
-    ensure!(argv.len() == 2, "usage: todos path/to/file/tree");
-    if argv[1] == "-h" || argv[1] == "--help" || argv[1] == "?" {
-        eprintln!("usage: todos path/to/file/tree");
-        eprintln!("Scans Rust files in the given tree for TODO-like comments");
-        eprintln!("and then prints all such comments, grouped by the TODO-");
-        eprintln!("like label (e.g., TODO-security)");
-        return Ok(());
+mod allow;
+mod baseline;
+mod config_file;
+mod diff;
+mod history;
+mod issues;
+mod json;
+#[cfg(feature = "protobuf")]
+mod protobuf;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod tags;
+mod timestamp;
+
+// Exit status contract.  CI scripts depend on being able to tell a policy
+// failure ("the tree has TODOs and you asked us to complain about that") from
+// a tool failure ("we couldn't scan the tree"), so these must stay distinct:
+//
+// - EXIT_OK: the scan completed and no findings gate tripped.
+// - EXIT_FOUND: the scan completed, but a findings gate (like
+//   `--error-if-found`, or `--strict` finding a malformed block comment)
+//   tripped.
+// - EXIT_ERROR: the scan itself failed, e.g., bad usage or an error that
+//   prevented us from scanning the tree.  Any `anyhow` error that makes it out
+//   of `run()` winds up here (except for a malformed comment, which some
+//   scans stop at with `--strict`).  Per-file problems that we warn about and
+//   skip don't count.
+
+/// exit status: the scan completed and no findings gate tripped
+const EXIT_OK: u8 = 0;
+/// exit status: the scan completed, but a findings gate tripped
+const EXIT_FOUND: u8 = 1;
+/// exit status: the scan itself failed
+const EXIT_ERROR: u8 = 2;
+
+const USAGE: &str = "usage: todos [OPTIONS] path/to/file/tree...
+       todos [OPTIONS] --stdin|- [--stdin-filename NAME]
+       todos [OPTIONS] --eval SOURCE [--stdin-filename NAME]
+       todos [OPTIONS] --staged
+       todos blame-report [OPTIONS] [REVISION_RANGE]
+       todos compare [OPTIONS] OLD_REVISION NEW_REVISION";
+const HELP: &str = "\
+Scans Rust files in the given trees for TODO-like comments
+and then prints all such comments, grouped by the TODO-
+like label (e.g., TODO-security).  A file given directly
+is scanned whatever its name.  C, C++, JavaScript, Python,
+and shell files are scanned too, judging only by what their
+comments look like.
+
+With \"blame-report\", instead of scanning a tree, walks the git history of
+the repository in the current directory and prints a CSV time series with the
+number of TODO-like lines added and removed by each commit in REVISION_RANGE
+(default: HEAD).
+
+With \"compare\", scans the files in two revisions of the repository in the
+current directory (without checking either one out) and prints how many
+TODO-like comments were added and removed between them, for each label.
+Comments that only moved to a different line don't count.
+
+Defaults for some options can be set in a .todos.toml file in the (first)
+tree being scanned or, failing that, in the current directory:
+
+    keywords = [\"TODO\", \"FIXME\", \"HACK\"]   # like --only for each
+    skip = [\"node_modules\"]                 # like --skip for each
+    format = \"json\"                         # like --format
+    ignore-case = true                      # like --ignore-case
+
+Options given on the command line take precedence over the file.
+
+Options:
+    --no-config         don't read a .todos.toml file
+    --error-if-found    exit with status 1 if any comments were found
+    --fail-on LABEL     exit with status 1 if any comments with LABEL were
+                        found (e.g., to allow TODO but not FIXME).  May be
+                        repeated.
+    --max N             exit with status 1 if more than N comments were found
+    --format FORMAT     how to print the report: \"text\" (the default),
+                        \"sexp\" (an s-expression that Lisp readers like
+                        Emacs's `read` can consume directly), \"rst\" (a
+                        reStructuredText document, e.g., for Sphinx),
+                        \"table\" (an aligned table that fits the terminal,
+                        or 80 columns when not writing to one), \"yaml\" (a
+                        list of comments, each with its label, file, line
+                        and column, and contents), \"git-grep\" (each line
+                        containing a label, as \"FILE:LINE:TEXT\" like `git
+                        grep -n`),
+                        \"lines\" (one line per comment, as
+                        \"FILE:LINE:COLUMN: LABEL: FIRST LINE\", for an
+                        editor's quickfix list),
+                        \"json\" (an object mapping each label to a list of
+                        comments, each with its file, line and column, and
+                        contents),
+                        \"github\" (a GitHub Actions workflow command per
+                        comment and label, which shows up as an annotation
+                        on that line of the pull request; use --relative-to
+                        if the tree isn't the top of the repository),
+                        \"sarif-fingerprints\" (a SARIF log for code
+                        scanning, e.g., on GitHub, with each comment's
+                        stable id as its fingerprint so that alerts follow
+                        comments that move), or
+                        \"html-dashboard\" (a self-contained HTML page with a
+                        sortable, filterable table and a chart of counts).
+                        Builds with the \"protobuf\" feature also support
+                        \"protobuf\": a length-delimited `Report` message, as
+                        described by proto/todos.proto.  Every format but
+                        git-grep gives each comment's location as the line
+                        and column where its label is.
+    --annotate-as-warning LABEL
+                        with --format github, annotate comments with LABEL
+                        as warnings rather than notices.  May be repeated.
+    --color WHEN        when to use color in the text report (bold labels,
+                        cyan file names, and dim line numbers): \"auto\"
+                        (the default) does so only when writing to a
+                        terminal and the NO_COLOR environment variable isn't
+                        set; the alternatives are \"always\" and \"never\"
+    --no-color          same as --color never
+    --hyperlinks WHEN   in the text report, make each comment's location a
+                        link that terminals can open (using OSC 8 escape
+                        sequences): \"auto\" (the default) does so only when
+                        writing to a terminal other than TERM=dumb; the
+                        alternatives are \"always\" and \"never\"
+    --issue-url-template TEMPLATE
+                        in the text report, show the URL of the issue each
+                        comment references (as in \"TODO#123\" or
+                        \"TODO(#123)\"), which is TEMPLATE with \"{}\"
+                        replaced by the issue number (e.g.,
+                        \"https://github.com/org/repo/issues/{}\")
+    --hyperlink-url TEMPLATE
+                        with hyperlinks, link to TEMPLATE, with \"{path}\"
+                        replaced by the absolute path of the file and
+                        \"{line}\" and \"{column}\" by where the label appears
+                        (default: \"file://{path}\").  For example,
+                        \"vscode://file{path}:{line}:{column}\" jumps to the
+                        label in VS Code.
+    --print0-matches    instead of the usual report, print one record per
+                        match as \"FILE:LINE:COLUMN:LABEL:FIRST_LINE\", each
+                        terminated by a NUL byte (e.g., for `fzf --read0`)
+    --occurrences       report each place a label appears, with its line and
+                        column, rather than each comment containing it (so a
+                        comment mentioning TODO twice counts twice).
+                        git-grep records become \"FILE:LINE:COLUMN:TEXT\"
+                        (like `git grep -n --column`).
+    --with-header       begin the report with a description of how it was
+                        produced: the version of todos, what was scanned,
+                        the keywords, scopes, and excludes.  In structured
+                        formats, this is included as metadata.  (The
+                        git-grep, github, and json formats and
+                        --print0-matches have no place for it.)
+    --split-output TEMPLATE
+                        instead of printing the report, write a separate
+                        report (in the --format given) for each label to the
+                        file named by TEMPLATE with \"{label}\" replaced by
+                        the label (e.g., \"reports/{label}.txt\").  Parent
+                        directories are created as needed.  Characters in
+                        the label other than letters, digits, \"-\", \"_\",
+                        and \".\" become \"_\".
+    --sqlite FILE       also write the comments (with their ids, tags,
+                        items, and so on) to the \"comments\" table of the
+                        SQLite database FILE, replacing any rows from a
+                        previous run.  The database and table are created if
+                        needed.  This needs a build with the \"sqlite\"
+                        feature and the sqlite3 command-line tool.
+    --exclude-if-matches REGEX
+                        leave out comments whose text matches REGEX,
+                        regardless of label (may be repeated)
+    --label GLOB        leave out comments whose label doesn't match GLOB
+                        (e.g., \"TODO-sec*\" matches \"TODO-security\" and
+                        \"TODO-seccomp\").  May be repeated to keep comments
+                        matching any of them.  Counts only include the
+                        comments kept.
+    --attached-only     leave out comments that aren't followed directly by
+                        code (e.g., ones followed by a blank line)
+    --exclude-docs      leave out doc comments (\"///\", \"//!\", \"/**\", and
+                        \"/*!\"), e.g., because they're user-facing
+    --only-docs         leave out everything but doc comments
+    --include-docs      include doc comments along with the rest (the
+                        default), undoing an earlier --exclude-docs or
+                        --only-docs
+    --expired           leave out all comments except those whose label has
+                        a year in parentheses that's already past (like
+                        \"TODO(2023)\"), list those on stderr, and exit with
+                        status 1 if there are any
+    --check-issues OWNER/REPO
+                        look up issues referenced by comments (as in
+                        \"TODO#123\" or \"TODO(#123)\") in this GitHub
+                        repository (uses curl and $GITHUB_TOKEN, if set)
+    --require-open-issue
+                        exit with status 1 if any comment doesn't reference
+                        an issue or references one that's closed or doesn't
+                        exist (requires --check-issues)
+    --no-public-doc-todos
+                        report comments found in doc comments (`///` or
+                        `/**`) on plain `pub` items, which rustdoc would
+                        publish, and exit with status 1 if there are any
+    --require-description
+                        report comments with nothing after the label (like
+                        a bare \"// TODO\" or \"// FIXME:\"), and
+                        todo!()-style macros without a message, and exit
+                        with status 1 if there are any
+    --keyword KEYWORD   also look for KEYWORD (e.g., \"HACK\"), besides the
+                        default keywords (XXX, FIXME, and TODO).  May be
+                        repeated.  As with the defaults, a word only has to
+                        start with KEYWORD, and a trailing \":\" isn't part of
+                        the label.  An empty keyword is an error, since it
+                        would match every word.
+    --only KEYWORD      look for KEYWORD instead of the default keywords
+                        (along with any given with --keyword).  May be
+                        repeated.
+    --ignore-case       match keywords regardless of case, reporting the
+                        keyword part of each label in uppercase (so \"todo\",
+                        \"Todo\", and \"TODO\" are all counted as \"TODO\")
+    --pattern REGEX     also report comments that match REGEX, labeled with
+                        the part matched by a group named \"label\" if it
+                        has one, or else the whole match (e.g.,
+                        \"@(?P<label>todo)\\b\" or \"!!!\").  May be repeated.
+    --scope GLOB=KEYWORD[,KEYWORD...]
+                        in files whose path (relative to the root of the
+                        tree) matches GLOB, look for these keywords instead
+                        of the default ones.  If
+                        several scopes match a file, the last one wins.  May
+                        be repeated.  A keyword containing spaces (e.g.,
+                        \"KNOWN ISSUE\") matches that phrase of whole words,
+                        even across lines.
+    --build-dirs NAME[,NAME...]
+                        skip directories with these names at the root of the
+                        tree, as build output (default: target).  This is
+                        separate from --skip, whose default list also has
+                        \"target\", so an empty list here still skips
+                        \"target\" (anywhere in the tree) unless --skip is
+                        given too.
+    --skip NAME         skip directories named NAME (e.g., \"node_modules\" or
+                        \"vendor\") wherever they are in the tree (default:
+                        target).  May be repeated.  The names given replace
+                        the default, so add \"target\" to keep skipping it.
+                        --build-dirs doesn't change this list.
+    --max-stored-per-label N
+                        keep at most N comments for each label.  Counts stay
+                        accurate, but the detailed listing (and checks like
+                        --require-open-issue) only cover the first N.
+    --scan-macros       also report invocations of todo!(), unimplemented!(),
+                        and unreachable!() in code, labeled with the macro's
+                        name (e.g., \"todo!\")
+    --scan-markdown     also report unchecked checklist items (like
+                        \"- [ ] TODO: ...\") in Markdown (.md) files
+    --split-on-dedent   end a run of `//` lines at a line that's less indented
+                        than the run's first line, treating what follows as
+                        a separate comment
+    --preserve-indent   keep comments' lines as they are in the source,
+                        indentation and all, rather than trimming them (for
+                        comments with tables or diagrams)
+    --stdin, -          instead of scanning a tree, scan source read from
+                        stdin (e.g., an editor's unsaved buffer)
+    --eval SOURCE       instead of scanning a tree, scan SOURCE itself (e.g.,
+                        \"// TODO test\"), for trying out options
+    --stdin-filename NAME
+                        with --stdin or --eval, report comments as coming
+                        from NAME (default: \"<stdin>\" or \"<eval>\")
+    --lang LANGUAGE     parse files whose extension doesn't say what language
+                        they're in (e.g., a script given directly, or stdin)
+                        as LANGUAGE: rust (the default), c, cpp, javascript,
+                        python, or shell
+    --staged            instead of scanning a tree, scan the changes staged
+                        in the git repository in the current directory,
+                        reporting only comments on changed lines (with paths
+                        relative to the top of the repository)
+    --warnings-as-errors
+                        fail (with exit status 1) if there were any warnings,
+                        like files that couldn't be read or that end inside
+                        a block comment
+    --age               in the text report's summary, show how long ago the
+                        oldest and newest comment with each label were
+                        written (according to `git blame`), and where they
+                        are
+    --blame             show who last changed the first line of each comment
+                        listed, and in which commit (according to `git
+                        blame`, run once per file).  This only applies to
+                        the text, json, and yaml reports.  Files outside a
+                        git repository are listed without it.
+    --group-threshold N in the text report's summary, count labels found
+                        fewer than N times together on one line instead of
+                        listing each of them.  (The comments themselves are
+                        still listed.)
+    --goal LABEL=COUNT  in the text report's summary, say how far the number of
+                        comments with LABEL is from COUNT (e.g., \"goal 30,
+                        over by 12\").  May be repeated.
+    --fail-over-goal    exit with status 1 if any label given with --goal was
+                        found more than its goal number of times
+    --by-extension      in the text report's summary, also count comments by
+                        the extension of their file (e.g., \".rs\").  Only
+                        the comments kept by --max-stored-per-label count.
+    --strip             in the text report, show each comment's text without
+                        its comment markers (\"//\", \"#\", \"/*\", \"*/\", and
+                        the \"*\" that starts lines inside a block comment)
+    --group-by label|file|assignee
+                        in the text report, group comments by label (the
+                        default), by file (listing each file's comments in
+                        line order), or by the name in their label's
+                        parentheses (e.g., \"alice\" for \"TODO(alice):\"),
+                        with those without one grouped as \"(unassigned)\".
+                        The summary counts each group.  Only the comments
+                        kept by --max-stored-per-label count.
+    --by-assignee       same as --group-by assignee
+    --count-only        print only the counts: in the text report, the
+                        summary without the comments themselves, and with
+                        --format json, an object mapping each label to its
+                        number of comments
+    --limit N           in the text report, list at most N comments in each
+                        group, noting how many more there are.  The summary
+                        still counts them all, and other formats (like JSON)
+                        list them all.
+    --sort label|count  in the text report, list the groups (and their rows
+                        in the summary) in order by label or other name
+                        (the default), or by count, largest first (ties
+                        listed by name)
+    --context N         in the text report, show N lines of source before and
+                        after each comment (marked with \"|\")
+    --warn-case-variants
+                        warn about labels that differ only by case (e.g.,
+                        \"TODO\" and \"Todo\")
+    --warn-intrafile-dupes
+                        warn about comments that appear more than once,
+                        word for word, in the same file (often a sign of
+                        copy-and-paste)
+    --exclude-glob GLOB skip files and directories whose path (relative to the
+                        root of the tree) matches GLOB.  A GLOB without a
+                        \"/\" can also match just the name.  May be repeated.
+    --ignore-file FILE  like --exclude-glob for each pattern in FILE, which
+                        has one per line (lines starting with \"#\" are
+                        ignored).  May be repeated.
+    --no-ignore         don't skip what .gitignore, .ignore, and
+                        .git/info/exclude files say to ignore.  (By default,
+                        those in the tree and in its parent directories, up
+                        to the top of its git repository, are honored.)
+    --follow-links, --follow-symlinks
+                        descend into symlinks to directories.  Symlinks to
+                        files are always scanned.  Each file and directory
+                        is visited only once, by whichever path is reached
+                        first, so nothing is counted twice, and loops are
+                        reported as warnings rather than followed.  The
+                        options that skip directories and files
+                        (--build-dirs, --skip, --exclude-glob, and ignore
+                        files) look at the path of the symlink, not where it
+                        leads.
+    --one-file-system   don't descend into directories on other file systems
+                        than the tree being scanned
+    --max-depth N       look at most N levels deep in the tree: with 1, scan
+                        only the files directly in it, with 2, also those in
+                        its subdirectories, and so on.  (With --staged, the
+                        top of the repository is the root of the tree.)
+    --strict            treat a block comment that runs to the end of its
+                        file, or (in Rust) a \"*/\" that doesn't end one, as
+                        an error rather than a warning: leave out that
+                        file's comments, report where the comment started,
+                        and exit with status 1.  With --staged, the first
+                        such file stops the scan.
+    --strict-files      stop (with exit status 2) at the first file or
+                        directory that can't be read, instead of warning
+                        about it and going on
+    --keep-going        warn about files and directories that can't be read
+                        and go on (the default; undoes --strict-files)
+    --threads N         read and parse files on at most N threads (default:
+                        one per CPU).  The report is the same either way.
+    --modified-since TIME
+                        skip files whose mtime is before TIME, which is
+                        either an RFC 3339 timestamp (e.g.,
+                        \"2023-01-31T12:00:00Z\") or a duration ago (e.g.,
+                        \"36h\" or \"7d\")
+    --min-filesize BYTES
+                        skip files smaller than BYTES
+    --max-filesize BYTES
+                        skip files larger than BYTES
+    --relative-to DIR   report file paths relative to DIR (e.g., the root of
+                        the repository while scanning a subdirectory of it)
+                        rather than as they were found by walking the tree
+                        from PATH.  This doesn't affect which files are
+                        scanned.
+    --baseline FILE     leave out comments listed in the baseline FILE (as
+                        written by --write-baseline), so that only new ones
+                        are reported and checked, and say on stderr how many
+                        listed there are gone.  Comments are matched by
+                        file, label, and text, not by line, so moving one
+                        doesn't make it new.
+    --write-baseline    with --baseline, write FILE listing every comment
+                        found (instead of leaving any out)
+    --allow FILE        leave out comments that have been accepted as they
+                        are, as listed in FILE, and say on stderr how many
+                        were left out.  Each line of FILE is either
+                        \"path/to/file.rs:LINE\", for the comment
+                        including that line, or a comment's content hash
+                        (as written by --write-allow), which stays the same
+                        when the comment is moved or reformatted.  Entries
+                        that match nothing are warned about.
+    --write-allow       with --allow, write FILE listing every comment found
+                        (instead of leaving any out)
+    --tags FILE         attach tags from FILE to the comments at the
+                        locations it lists.  Each line of FILE looks like
+                        \"path/to/file.rs:LINE TAG\", with paths relative to
+                        the root of the tree.
+    --profile-output    after the report, print a table of the time spent in
+                        each phase of the run (walking the tree, reading
+                        files, parsing them, and looking up issues) to stderr.
+                        With several threads, the times for reading and
+                        parsing are totals across them.
+    --stats             after the report, print statistics to stderr: how
+                        many files were scanned (not counting any that
+                        couldn't be read) and skipped, how many had
+                        comments, the files with the most comments, and
+                        each label's share of all the comments found (a
+                        comment with several labels counts for each)
+    --quiet, -q         don't print progress messages (the files being read
+                        and those being skipped) to stderr.  Warnings are
+                        still printed.
+    --watch             after printing the report, keep running, and print it
+                        again (clearing the terminal first) whenever a file
+                        that the scan looks at is changed, added, or
+                        removed.  Stop with Ctrl-C.
+    --verbose           (compare only) also list each comment that was added
+                        or removed
+    --max-count N       (blame-report only) report on at most the N most
+                        recent commits (default: 100)
+
+Exit status:
+    0    the scan completed and no check failed
+    1    the scan completed, but a check like --error-if-found, --fail-on,
+         --max, --require-open-issue, --warnings-as-errors, or --strict
+         failed
+    2    the scan itself failed
+";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => ExitCode::from(code),
+        Err(error) => {
+            eprintln!("error: {:#}", error);
+            // A malformed comment stops some scans (e.g., with --staged), but
+            // with --strict, that's a findings gate like any other.
+            if error.chain().any(|e| e.is::<MalformedComment>()) {
+                ExitCode::from(EXIT_FOUND)
+            } else {
+                ExitCode::from(EXIT_ERROR)
+            }
+        }
+    }
+}
+
+/// What we've been asked to do
+#[derive(Default)]
+enum Command {
+    /// scan a tree and report on the comments in it
+    #[default]
+    Scan,
+    /// report TODO lines added and removed over git history
+    BlameReport,
+    /// report comments added and removed between two revisions
+    Compare,
+}
+
+/// How to print the report
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Format {
+    /// human-readable text
+    #[default]
+    Text,
+    /// an s-expression, for Lisp (e.g., Emacs) tooling
+    Sexp,
+    /// a reStructuredText document
+    Rst,
+    /// a self-contained, interactive HTML page
+    HtmlDashboard,
+    /// an aligned ASCII table
+    Table,
+    /// a YAML list of comments
+    Yaml,
+    /// matching lines, like `git grep -n`
+    GitGrep,
+    /// one line per comment, for editors' quickfix lists
+    Lines,
+    /// a JSON object mapping each label to its comments
+    Json,
+    /// GitHub Actions workflow commands that annotate each comment
+    Github,
+    /// a SARIF log, with stable fingerprints for each comment
+    SarifFingerprints,
+    /// a length-delimited protobuf `Report` message
+    #[cfg(feature = "protobuf")]
+    Protobuf,
+}
+
+impl Format {
+    fn parse(name: &str) -> Result<Format, anyhow::Error> {
+        match name {
+            "text" => Ok(Format::Text),
+            "sexp" | "s-expr" => Ok(Format::Sexp),
+            "rst" => Ok(Format::Rst),
+            "html-dashboard" => Ok(Format::HtmlDashboard),
+            "table" | "ascii-table" => Ok(Format::Table),
+            "yaml" => Ok(Format::Yaml),
+            "git-grep" => Ok(Format::GitGrep),
+            "lines" => Ok(Format::Lines),
+            "json" => Ok(Format::Json),
+            "github" => Ok(Format::Github),
+            "sarif-fingerprints" => Ok(Format::SarifFingerprints),
+            #[cfg(feature = "protobuf")]
+            "protobuf" => Ok(Format::Protobuf),
+            #[cfg(not(feature = "protobuf"))]
+            "protobuf" => {
+                bail!("this build of todos doesn't support --format protobuf")
+            }
+            _ => bail!(
+                "unsupported format {:?} (expected text, sexp, rst, \
+                 html-dashboard, table, yaml, git-grep, lines, json, \
+                 github, or sarif-fingerprints)",
+                name
+            ),
+        }
+    }
+}
+
+/// When to use color or hyperlinks in the text report
+#[derive(Clone, Copy, Default, PartialEq)]
+enum When {
+    /// when writing to a terminal that's likely to support them
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl When {
+    /// Parses `when`, the value given for `option`
+    fn parse(option: &str, when: &str) -> Result<When, anyhow::Error> {
+        match when {
+            "auto" => Ok(When::Auto),
+            "always" => Ok(When::Always),
+            "never" => Ok(When::Never),
+            _ => bail!(
+                "unsupported value {:?} for {} (expected auto, always, or \
+                 never)",
+                when,
+                option
+            ),
+        }
+    }
+}
+
+/// How the text report groups comments
+#[derive(Clone, Copy, Default, PartialEq)]
+enum GroupBy {
+    #[default]
+    Label,
+    File,
+    /// by the name in the label's parentheses, as in "TODO(alice)"
+    Assignee,
+}
+
+impl GroupBy {
+    fn parse(by: &str) -> Result<GroupBy, anyhow::Error> {
+        match by {
+            "label" => Ok(GroupBy::Label),
+            "file" => Ok(GroupBy::File),
+            "assignee" => Ok(GroupBy::Assignee),
+            _ => bail!(
+                "unsupported value {:?} for --group-by (expected label, \
+                 file, or assignee)",
+                by
+            ),
+        }
+    }
+
+    /// Returns the option that selects this grouping, for error messages
+    fn option(self) -> &'static str {
+        match self {
+            GroupBy::Label => "--group-by label",
+            GroupBy::File => "--group-by file",
+            GroupBy::Assignee => "--by-assignee",
+        }
+    }
+}
+
+/// Which comments to keep, depending on whether they're doc comments
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Docs {
+    #[default]
+    Include,
+    Exclude,
+    Only,
+}
+
+/// How the text report orders groups of comments
+#[derive(Clone, Copy, Default, PartialEq)]
+enum SortBy {
+    /// by label (or file or assignee)
+    #[default]
+    Name,
+    /// by number of comments, largest first
+    Count,
+}
+
+impl SortBy {
+    fn parse(by: &str) -> Result<SortBy, anyhow::Error> {
+        match by {
+            "label" => Ok(SortBy::Name),
+            "count" => Ok(SortBy::Count),
+            _ => bail!(
+                "unsupported value {:?} for --sort (expected label or count)",
+                by
+            ),
+        }
+    }
+}
+
+/// Command-line options
+#[derive(Default)]
+struct Args {
+    command: Command,
+    /// roots of the file trees to scan
+    paths: Vec<OsString>,
+    /// (blame-report only) range of commits to report on
+    range: Option<String>,
+    /// (blame-report only) maximum number of commits to report on
+    max_count: Option<usize>,
+    /// (compare only) the revisions to compare
+    revisions: Vec<String>,
+    /// (compare only) list the comments added and removed
+    verbose: bool,
+    /// exit with `EXIT_FOUND` if we found any comments at all
+    error_if_found: bool,
+    /// exit with `EXIT_FOUND` if any comments with these labels are found
+    fail_on: BTreeSet<String>,
+    /// (github only) labels to annotate as warnings rather than notices
+    annotate_as_warning: BTreeSet<String>,
+    /// exit with `EXIT_FOUND` if more than this many comments are found
+    max: Option<usize>,
+    /// how to print the report
+    format: Format,
+    /// print NUL-terminated match records instead of the usual report
+    print0_matches: bool,
+    /// report each occurrence of a label rather than each comment
+    occurrences: bool,
+    /// write a report per label to files named by this template
+    split_output: Option<String>,
+    /// SQLite database to write the comments to
+    sqlite: Option<PathBuf>,
+    /// describe how the report was produced at the top of it
+    with_header: bool,
+    /// leave out comments whose contents match any of these
+    exclude_if_matches: Vec<Regex>,
+    /// if non-empty, leave out comments whose label matches none of these
+    labels: Vec<Glob>,
+    /// leave out comments that aren't followed directly by code
+    attached_only: bool,
+    /// whether to keep doc comments, other comments, or both
+    docs: Docs,
+    /// keep only comments whose year tag is past, and fail if there are any
+    expired: bool,
+    /// GitHub repository ("owner/name") to look up referenced issues in
+    check_issues: Option<String>,
+    /// exit with `EXIT_FOUND` if any comment doesn't reference an open issue
+    require_open_issue: bool,
+    /// exit with `EXIT_FOUND` if any comment is in the docs of a `pub` item
+    no_public_doc_todos: bool,
+    /// exit with `EXIT_FOUND` if any comment has nothing after its label
+    require_description: bool,
+    /// report file paths relative to this directory
+    relative_to: Option<PathBuf>,
+    /// file mapping comment locations to tags
+    tags: Option<String>,
+    /// leave out comments listed in this baseline file (or, with
+    /// `write_baseline`, write it)
+    baseline: Option<String>,
+    /// write the baseline file rather than reading it
+    write_baseline: bool,
+    /// leave out comments listed in this allowlist file (or, with
+    /// `write_allow`, write it)
+    allow: Option<String>,
+    /// write the allowlist file rather than reading it
+    write_allow: bool,
+    /// in the summary, lump together labels found fewer times than this
+    group_threshold: Option<usize>,
+    /// target number of comments for some labels
+    goals: BTreeMap<String, usize>,
+    /// exit with `EXIT_FOUND` if any label is over its goal
+    fail_over_goal: bool,
+    /// in the summary, also count comments by file extension
+    by_extension: bool,
+    /// how to group comments
+    group_by: GroupBy,
+    /// how to order groups of comments
+    sort: SortBy,
+    /// maximum number of comments to list in each group
+    limit: Option<usize>,
+    /// print only the counts, not the comments
+    count_only: bool,
+    /// show comments without their comment markers
+    strip: bool,
+    /// maximum number of comments to keep for each label
+    max_stored_per_label: Option<usize>,
+    /// scan source from stdin instead of a tree
+    stdin: bool,
+    /// scan this source instead of a tree
+    eval: Option<String>,
+    /// with `stdin`, the file name to report
+    stdin_filename: Option<String>,
+    /// scan the changes staged in git instead of a tree
+    staged: bool,
+    /// show the oldest and newest comment for each label in the summary
+    age: bool,
+    /// look up who last changed each comment with `git blame`
+    blame: bool,
+    /// warn about labels that differ only by case
+    warn_case_variants: bool,
+    /// warn about comments repeated within a file
+    warn_intrafile_dupes: bool,
+    /// when to use color, if given
+    color: Option<When>,
+    /// keywords to look for besides the default (or `only_keywords`)
+    extra_keywords: Vec<String>,
+    /// keywords to look for instead of the default
+    only_keywords: Vec<String>,
+    /// when to make locations into hyperlinks, if given
+    hyperlinks: Option<When>,
+    /// the URL that hyperlinks point to, if not the file itself
+    hyperlink_url: Option<String>,
+    /// the URL of referenced issues, with "{}" for the issue number
+    issue_url: Option<String>,
+    /// fail if there were any warnings
+    warnings_as_errors: bool,
+    /// print counts of files scanned and skipped
+    stats: bool,
+    /// print the time spent in each phase of the run
+    profile_output: bool,
+    /// scan again whenever the files change
+    watch: bool,
+    /// settings for the scan itself
+    config: Config,
+}
+
+/// Parses command-line arguments, returning `None` if the user just wanted
+/// help (which we've already printed)
+fn parse_args() -> Result<Option<Args>, anyhow::Error> {
+    let mut args = Args::default();
+    args.config.progress = true;
+    let mut positional = Vec::new();
+    let mut format = None;
+    let mut skip_dirs = Vec::new();
+    let mut read_config_file = true;
+
+    let mut argv = std::env::args_os().skip(1).peekable();
+    if argv.peek().is_some_and(|arg| arg == "blame-report") {
+        argv.next();
+        args.command = Command::BlameReport;
+    } else if argv.peek().is_some_and(|arg| arg == "compare") {
+        argv.next();
+        args.command = Command::Compare;
+    }
+
+    while let Some(arg) = argv.next() {
+        if arg == "-h" || arg == "--help" || arg == "?" {
+            eprint!("{}\n{}", USAGE, HELP);
+            return Ok(None);
+        } else if arg == "--no-config" {
+            read_config_file = false;
+        } else if arg == "--quiet" || arg == "-q" {
+            args.config.progress = false;
+        } else if arg == "--error-if-found" {
+            args.error_if_found = true;
+        } else if arg == "--fail-on" {
+            args.fail_on.insert(option_value(&mut argv, &arg)?);
+        } else if arg == "--annotate-as-warning" {
+            args.annotate_as_warning.insert(option_value(&mut argv, &arg)?);
+        } else if arg == "--max" {
+            let max = option_value(&mut argv, &arg)?;
+            args.max = Some(max.parse().with_context(|| {
+                format!("invalid value for {:?}: {:?}", arg, max)
+            })?);
+        } else if arg == "--format" {
+            format = Some(Format::parse(&option_value(&mut argv, &arg)?)?);
+        } else if arg == "--print0-matches" {
+            args.print0_matches = true;
+        } else if arg == "--occurrences" {
+            args.occurrences = true;
+        } else if arg == "--with-header" {
+            args.with_header = true;
+        } else if arg == "--split-output" {
+            let template = option_value(&mut argv, &arg)?;
+            if !template.contains("{label}") {
+                bail!("--split-output template must contain \"{{label}}\"");
+            }
+            args.split_output = Some(template);
+        } else if arg == "--sqlite" {
+            args.sqlite = Some(PathBuf::from(option_value(&mut argv, &arg)?));
+        } else if arg == "--label" {
+            args.labels.push(Glob::new(&option_value(&mut argv, &arg)?)?);
+        } else if arg == "--exclude-if-matches" {
+            let pattern = option_value(&mut argv, &arg)?;
+            args.exclude_if_matches.push(Regex::new(&pattern)?);
+        } else if arg == "--attached-only" {
+            args.attached_only = true;
+        } else if arg == "--include-docs" {
+            args.docs = Docs::Include;
+        } else if arg == "--exclude-docs" {
+            args.docs = Docs::Exclude;
+        } else if arg == "--only-docs" {
+            args.docs = Docs::Only;
+        } else if arg == "--expired" {
+            args.expired = true;
+        } else if arg == "--check-issues" {
+            args.check_issues = Some(option_value(&mut argv, &arg)?);
+        } else if arg == "--require-open-issue" {
+            args.require_open_issue = true;
+        } else if arg == "--no-public-doc-todos" {
+            args.no_public_doc_todos = true;
+        } else if arg == "--require-description" {
+            args.require_description = true;
+        } else if arg == "--keyword" {
+            args.extra_keywords.push(keyword(option_value(&mut argv, &arg)?)?);
+        } else if arg == "--ignore-case" {
+            args.config.ignore_case = true;
+        } else if arg == "--pattern" {
+            let pattern = option_value(&mut argv, &arg)?;
+            args.config.patterns.push(Regex::new(&pattern)?);
+        } else if arg == "--only" {
+            args.only_keywords.push(keyword(option_value(&mut argv, &arg)?)?);
+        } else if arg == "--scope" {
+            let scope = option_value(&mut argv, &arg)?;
+            args.config.keywords.scopes.push(parse_scope(&scope)?);
+        } else if arg == "--max-stored-per-label" {
+            let max = option_value(&mut argv, &arg)?;
+            args.max_stored_per_label =
+                Some(max.parse().with_context(|| {
+                    format!("invalid value for {:?}: {:?}", arg, max)
+                })?);
+        } else if arg == "--goal" {
+            let goal = option_value(&mut argv, &arg)?;
+            let (label, count) = parse_goal(&goal)?;
+            args.goals.insert(label, count);
+        } else if arg == "--fail-over-goal" {
+            args.fail_over_goal = true;
+        } else if arg == "--by-extension" {
+            args.by_extension = true;
+        } else if arg == "--strip" {
+            args.strip = true;
+        } else if arg == "--by-assignee" {
+            args.group_by = GroupBy::Assignee;
+        } else if arg == "--count-only" {
+            args.count_only = true;
+        } else if arg == "--limit" {
+            let limit = option_value(&mut argv, &arg)?;
+            args.limit = Some(limit.parse().with_context(|| {
+                format!("invalid value for {:?}: {:?}", arg, limit)
+            })?);
+        } else if arg == "--sort" {
+            args.sort = SortBy::parse(&option_value(&mut argv, &arg)?)?;
+        } else if arg == "--group-by" {
+            args.group_by = GroupBy::parse(&option_value(&mut argv, &arg)?)?;
+        } else if arg == "--context" {
+            let lines = option_value(&mut argv, &arg)?;
+            args.config.context_lines = lines.parse().with_context(|| {
+                format!("invalid value for {:?}: {:?}", arg, lines)
+            })?;
+        } else if arg == "--group-threshold" {
+            let threshold = option_value(&mut argv, &arg)?;
+            args.group_threshold =
+                Some(threshold.parse().with_context(|| {
+                    format!("invalid value for {:?}: {:?}", arg, threshold)
+                })?);
+        } else if arg == "--scan-markdown" {
+            args.config.scan_markdown = true;
+        } else if arg == "--scan-macros" {
+            args.config.scan_macros = true;
+        } else if arg == "--split-on-dedent" {
+            args.config.split_on_dedent = true;
+        } else if arg == "--preserve-indent" {
+            args.config.preserve_indent = true;
+        } else if arg == "--stdin" {
+            args.stdin = true;
+        } else if arg == "--eval" {
+            args.eval = Some(option_value(&mut argv, &arg)?);
+        } else if arg == "--staged" {
+            args.staged = true;
+        } else if arg == "--stdin-filename" {
+            args.stdin_filename = Some(option_value(&mut argv, &arg)?);
+        } else if arg == "--lang" {
+            let name = option_value(&mut argv, &arg)?;
+            let syntax = lang::syntax_named(&name).ok_or_else(|| {
+                anyhow!(
+                    "unknown language {:?} (expected one of: {})",
+                    name,
+                    lang::LANGUAGES
+                        .iter()
+                        .map(|(name, _, _)| *name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+            args.config.lang = Some(syntax);
+        } else if arg == "--no-color" {
+            args.color = Some(When::Never);
+        } else if arg == "--color" {
+            let when = option_value(&mut argv, &arg)?;
+            args.color = Some(When::parse("--color", &when)?);
+        } else if arg == "--hyperlinks" {
+            let when = option_value(&mut argv, &arg)?;
+            args.hyperlinks = Some(When::parse("--hyperlinks", &when)?);
+        } else if arg == "--issue-url-template" {
+            let template = option_value(&mut argv, &arg)?;
+            if !template.contains("{}") {
+                bail!("--issue-url-template template must contain \"{{}}\"");
+            }
+            args.issue_url = Some(template);
+        } else if arg == "--hyperlink-url" {
+            args.hyperlink_url = Some(option_value(&mut argv, &arg)?);
+        } else if arg == "--warnings-as-errors" {
+            args.warnings_as_errors = true;
+        } else if arg == "--age" {
+            args.age = true;
+        } else if arg == "--blame" {
+            args.blame = true;
+        } else if arg == "--warn-case-variants" {
+            args.warn_case_variants = true;
+        } else if arg == "--warn-intrafile-dupes" {
+            args.warn_intrafile_dupes = true;
+        } else if arg == "--exclude-glob" {
+            let pattern = option_value(&mut argv, &arg)?;
+            args.config.exclude.push(Glob::new(&pattern)?);
+        } else if arg == "--ignore-file" {
+            let path = option_value(&mut argv, &arg)?;
+            args.config.exclude.extend(glob::load_patterns(Path::new(&path))?);
+        } else if arg == "--strict" {
+            args.config.strict_comments = true;
+        } else if arg == "--strict-files" {
+            args.config.strict_files = true;
+        } else if arg == "--keep-going" {
+            args.config.strict_files = false;
+        } else if arg == "--threads" {
+            let threads = option_value(&mut argv, &arg)?;
+            match threads.parse() {
+                Ok(0) => bail!("--threads must be at least 1"),
+                Ok(n) => args.config.threads = Some(n),
+                Err(_) => bail!("invalid value for {:?}: {:?}", arg, threads),
+            }
+        } else if arg == "--no-ignore" {
+            args.config.ignore_files = false;
+        } else if arg == "--follow-links" || arg == "--follow-symlinks" {
+            args.config.follow_links = true;
+        } else if arg == "--one-file-system" {
+            args.config.same_file_system = true;
+        } else if arg == "--max-depth" {
+            let depth = option_value(&mut argv, &arg)?;
+            match depth.parse() {
+                Ok(0) => bail!("--max-depth must be at least 1"),
+                Ok(n) => args.config.max_depth = Some(n),
+                Err(_) => bail!("invalid value for {:?}: {:?}", arg, depth),
+            }
+        } else if arg == "--min-filesize" || arg == "--max-filesize" {
+            let value = option_value(&mut argv, &arg)?;
+            let bytes = Some(value.parse().with_context(|| {
+                format!("invalid value for {:?}: {:?}", arg, value)
+            })?);
+            if arg == "--min-filesize" {
+                args.config.min_filesize = bytes;
+            } else {
+                args.config.max_filesize = bytes;
+            }
+        } else if arg == "--modified-since" {
+            let since = option_value(&mut argv, &arg)?;
+            args.config.modified_since =
+                Some(timestamp::parse_since(&since, SystemTime::now())?);
+        } else if arg == "--relative-to" {
+            args.relative_to =
+                Some(PathBuf::from(option_value(&mut argv, &arg)?));
+        } else if arg == "--tags" {
+            args.tags = Some(option_value(&mut argv, &arg)?);
+        } else if arg == "--baseline" {
+            args.baseline = Some(option_value(&mut argv, &arg)?);
+        } else if arg == "--write-baseline" {
+            args.write_baseline = true;
+        } else if arg == "--allow" {
+            args.allow = Some(option_value(&mut argv, &arg)?);
+        } else if arg == "--write-allow" {
+            args.write_allow = true;
+        } else if arg == "--watch" {
+            args.watch = true;
+        } else if arg == "--stats" {
+            args.stats = true;
+        } else if arg == "--profile-output" {
+            args.profile_output = true;
+        } else if arg == "--skip" {
+            skip_dirs.push(option_value(&mut argv, &arg)?);
+        } else if arg == "--build-dirs" {
+            let dirs = option_value(&mut argv, &arg)?;
+            args.config.build_dirs = dirs
+                .split(',')
+                .map(|d| d.trim().to_string())
+                .filter(|d| !d.is_empty())
+                .collect();
+        } else if arg == "--verbose" {
+            args.verbose = true;
+        } else if arg == "--max-count" {
+            let value = option_value(&mut argv, &arg)?;
+            args.max_count = Some(value.parse().with_context(|| {
+                format!("invalid value for {:?}: {:?}", arg, value)
+            })?);
+        } else if arg != "-" && arg.to_string_lossy().starts_with('-') {
+            bail!("unrecognized option {:?}\n{}", arg, USAGE);
+        } else {
+            positional.push(arg);
+        }
+    }
+
+    if args.max_count.is_some() && !matches!(args.command, Command::BlameReport)
+    {
+        bail!("--max-count only applies to blame-report");
+    }
+    if args.verbose && !matches!(args.command, Command::Compare) {
+        bail!("--verbose only applies to compare");
+    }
+    if args.watch && !matches!(args.command, Command::Scan) {
+        bail!("--watch cannot be combined with blame-report or compare");
+    }
+
+    match args.command {
+        Command::BlameReport => {
+            if positional.len() > 1 {
+                bail!("{}", USAGE);
+            }
+            args.range = positional.pop().map(revision).transpose()?;
+            return Ok(Some(args));
+        }
+        Command::Compare => {
+            if positional.len() != 2 {
+                bail!("{}", USAGE);
+            }
+            args.revisions = positional
+                .into_iter()
+                .map(revision)
+                .collect::<Result<_, _>>()?;
+            return Ok(Some(args));
+        }
+        Command::Scan => (),
+    }
+
+    let mut paths = positional;
+    // As with many tools, a path of "-" means stdin.
+    if paths.iter().any(|p| p == "-") {
+        if paths.len() > 1 {
+            bail!("\"-\" (stdin) cannot be combined with other paths to scan");
+        }
+        if args.stdin {
+            bail!("\"-\" and --stdin cannot both be given");
+        }
+        paths.clear();
+        args.stdin = true;
+    }
+    if args.stdin && args.eval.is_some() {
+        bail!("--stdin cannot be combined with --eval");
+    }
+    if args.stdin || args.eval.is_some() {
+        if !paths.is_empty() {
+            bail!(
+                "{} cannot be combined with a path to scan",
+                if args.stdin { "--stdin" } else { "--eval" }
+            );
+        }
+        if args.relative_to.is_some() {
+            bail!("--relative-to cannot be combined with --stdin or --eval");
+        }
+        if args.staged {
+            bail!("--staged cannot be combined with --stdin or --eval");
+        }
+        if args.watch {
+            bail!("--watch cannot be combined with --stdin or --eval");
+        }
+        if args.blame {
+            bail!("--blame cannot be combined with --stdin or --eval");
+        }
+    } else if args.stdin_filename.is_some() {
+        bail!("--stdin-filename requires --stdin or --eval");
+    } else if args.staged {
+        if !paths.is_empty() {
+            bail!("--staged cannot be combined with a path to scan");
+        }
+        if args.watch {
+            bail!("--watch cannot be combined with --staged");
+        }
+        if args.blame {
+            bail!("--blame cannot be combined with --staged");
+        }
+    } else if paths.is_empty() {
+        bail!("{}", USAGE);
+    } else {
+        args.paths = paths;
+    }
+
+    // Fill in whatever wasn't given on the command line from the config file.
+    let (config_path, config_file) = if read_config_file {
+        let root = args.paths.first().map(Path::new).filter(|p| p.is_dir());
+        ConfigFile::find(root.into_iter().chain([Path::new(".")]))?
+            .unwrap_or_default()
+    } else {
+        Default::default()
+    };
+    args.format = match (format, config_file.format) {
+        (Some(format), _) => format,
+        (None, Some(name)) => Format::parse(&name)
+            .with_context(|| format!("parse {:?}", config_path.display()))?,
+        (None, None) => Format::default(),
+    };
+    // Either --skip or the config file replaces the default list (rather than
+    // adding to it), like --build-dirs does.
+    if !skip_dirs.is_empty() {
+        args.config.skip_dirs = skip_dirs;
+    } else if let Some(skip) = config_file.skip {
+        args.config.skip_dirs = skip;
+    }
+    args.config.ignore_case |= config_file.ignore_case.unwrap_or(false);
+    if args.only_keywords.is_empty() {
+        if let Some(keywords) = config_file.keywords {
+            args.only_keywords = keywords;
+        }
+    }
+
+    if !args.only_keywords.is_empty() {
+        args.config.keywords.default = std::mem::take(&mut args.only_keywords);
+    }
+    for keyword in std::mem::take(&mut args.extra_keywords) {
+        if !args.config.keywords.default.contains(&keyword) {
+            args.config.keywords.default.push(keyword);
+        }
+    }
+    if let (Some(min), Some(max)) =
+        (args.config.min_filesize, args.config.max_filesize)
+    {
+        if min > max {
+            bail!("--min-filesize is larger than --max-filesize");
+        }
+    }
+    if args.require_open_issue && args.check_issues.is_none() {
+        bail!("--require-open-issue requires --check-issues");
+    }
+    if args.print0_matches && args.format != Format::Text {
+        bail!("--print0-matches cannot be combined with --format");
+    }
+    if !args.annotate_as_warning.is_empty() && args.format != Format::Github {
+        bail!("--annotate-as-warning only applies to --format github");
+    }
+    if args.age && (args.format != Format::Text || args.print0_matches) {
+        bail!("--age only applies to the text report");
+    }
+    if args.blame
+        && (!matches!(args.format, Format::Text | Format::Json | Format::Yaml)
+            || args.print0_matches)
+    {
+        bail!("--blame only applies to the text, json, and yaml reports");
+    }
+    if args.group_threshold.is_some()
+        && (args.format != Format::Text || args.print0_matches)
+    {
+        bail!("--group-threshold only applies to the text report");
+    }
+    if args.fail_over_goal && args.goals.is_empty() {
+        bail!("--fail-over-goal requires --goal");
+    }
+    if args.by_extension && (args.format != Format::Text || args.print0_matches)
+    {
+        bail!("--by-extension only applies to the text report");
+    }
+    if (args.hyperlinks.is_some() || args.hyperlink_url.is_some())
+        && (args.format != Format::Text || args.print0_matches)
+    {
+        bail!("--hyperlinks only applies to the text report");
+    }
+    if args.issue_url.is_some()
+        && (args.format != Format::Text || args.print0_matches)
+    {
+        bail!("--issue-url-template only applies to the text report");
+    }
+    if args.hyperlink_url.is_some() && args.hyperlinks == Some(When::Never) {
+        bail!("--hyperlink-url cannot be combined with --hyperlinks never");
+    }
+    if args.write_baseline && args.baseline.is_none() {
+        bail!("--write-baseline requires --baseline");
+    }
+    if args.write_allow && args.allow.is_none() {
+        bail!("--write-allow requires --allow");
+    }
+    if args.sort != SortBy::Name
+        && (args.format != Format::Text || args.print0_matches)
+    {
+        bail!("--sort only applies to the text report");
+    }
+    if args.count_only
+        && (!matches!(args.format, Format::Text | Format::Json)
+            || args.print0_matches)
+    {
+        bail!("--count-only only applies to the text and json reports");
+    }
+    if args.strip && (args.format != Format::Text || args.print0_matches) {
+        bail!("--strip only applies to the text report");
+    }
+    if args.config.context_lines > 0
+        && (args.format != Format::Text || args.print0_matches)
+    {
+        bail!("--context only applies to the text report");
+    }
+    if args.group_by != GroupBy::Label {
+        let option = args.group_by.option();
+        if args.format != Format::Text || args.print0_matches {
+            bail!("{} only applies to the text report", option);
+        }
+        if args.age || args.group_threshold.is_some() || !args.goals.is_empty()
+        {
+            bail!(
+                "{} cannot be combined with --age, --group-threshold, or \
+                 --goal",
+                option
+            );
+        }
+    }
+    if cfg!(not(feature = "sqlite")) && args.sqlite.is_some() {
+        bail!("this build of todos doesn't support --sqlite");
+    }
+    if args.print0_matches && args.split_output.is_some() {
+        bail!("--print0-matches cannot be combined with --split-output");
+    }
+    Ok(Some(args))
+}
+
+/// Converts a revision (or range) given on the command line to a `String`
+fn revision(arg: OsString) -> Result<String, anyhow::Error> {
+    arg.into_string()
+        .map_err(|arg| anyhow!("revision is not valid UTF-8: {:?}", arg))
+}
+
+/// Checks the value of a `--keyword` or `--only` option
+fn keyword(keyword: String) -> Result<String, anyhow::Error> {
+    if keyword.trim().is_empty() {
+        bail!("keywords cannot be empty (that would match every word)");
+    }
+    Ok(keyword.trim().to_string())
+}
+
+/// Parses the value of a `--scope` option, which looks like
+/// "GLOB=KEYWORD[,KEYWORD...]"
+fn parse_scope(scope: &str) -> Result<(Glob, Vec<String>), anyhow::Error> {
+    let (pattern, keywords) = scope.rsplit_once('=').ok_or_else(|| {
+        anyhow!("invalid scope {:?}: expected GLOB=KEYWORD[,...]", scope)
+    })?;
+    let keywords: Vec<String> = keywords
+        .split(',')
+        .map(|k| k.trim().to_string())
+        .filter(|k| !k.is_empty())
+        .collect();
+    if keywords.is_empty() {
+        bail!("invalid scope {:?}: no keywords given", scope);
+    }
+    Ok((Glob::new(pattern)?, keywords))
+}
+
+/// Parses the value of a `--goal` option, like "TODO=30"
+fn parse_goal(goal: &str) -> Result<(String, usize), anyhow::Error> {
+    let error = || anyhow!("invalid goal {:?}: expected LABEL=COUNT", goal);
+    let (label, count) = goal.rsplit_once('=').ok_or_else(error)?;
+    let count = count.trim().parse().map_err(|_| error())?;
+    if label.is_empty() {
+        return Err(error());
+    }
+    Ok((label.to_string(), count))
+}
+
+/// Returns the value for command-line option `option`, which must be the next
+/// argument
+fn option_value(
+    argv: &mut impl Iterator<Item = OsString>,
+    option: &OsString,
+) -> Result<String, anyhow::Error> {
+    let value = argv
+        .next()
+        .ok_or_else(|| anyhow!("option {:?} requires a value", option))?;
+    value.into_string().map_err(|value| {
+        anyhow!("value for option {:?} is not valid UTF-8: {:?}", option, value)
+    })
+}
+
+/// Runs the whole program, returning the exit status to use on success
+fn run() -> Result<u8, anyhow::Error> {
+    let args = match parse_args()? {
+        Some(args) => args,
+        None => return Ok(EXIT_OK),
+    };
+
+    // Stdout is line-buffered, so each line of output shows up as soon as
+    // it's written.  If whoever's reading it goes away (as with `todos . |
+    // head`), we stop writing but otherwise carry on, so that the exit status
+    // still reflects the checks.
+    let mut stdout = std::io::stdout().lock();
+
+    if let Command::BlameReport = args.command {
+        ignore_broken_pipe(history::blame_report(
+            &mut stdout,
+            Path::new("."),
+            args.range.as_deref().unwrap_or("HEAD"),
+            args.max_count.unwrap_or(100),
+            &args.config.keywords,
+        ))?;
+        return Ok(EXIT_OK);
+    }
+
+    if let Command::Compare = args.command {
+        ignore_broken_pipe(compare(
+            &mut stdout,
+            &args.revisions[0],
+            &args.revisions[1],
+            &args,
+        ))?;
+        return Ok(EXIT_OK);
+    }
+
+    if args.watch {
+        watch(&args, &mut stdout)
+    } else {
+        scan_and_report(&args, &mut stdout)
+    }
+}
+
+/// Scans what `args` says to, prints the report to `stdout` (and anything
+/// else asked for to stderr), and returns the exit status
+fn scan_and_report(
+    args: &Args,
+    stdout: &mut dyn Write,
+) -> Result<u8, anyhow::Error> {
+    // If we can't even read the root of a tree, there's no point in going
+    // on: that's a failure of the scan, not something to warn about and skip.
+    let literal = args.stdin || args.eval.is_some();
+    let roots: Vec<&Path> = if literal || args.staged {
+        vec![Path::new(".")]
+    } else {
+        args.paths.iter().map(Path::new).collect()
+    };
+    if !literal && !args.staged {
+        for root in &roots {
+            std::fs::metadata(root)
+                .with_context(|| format!("scan {:?}", root))?;
+        }
+    }
+
+    let tags = match &args.tags {
+        Some(path) => Some(TagMap::load(Path::new(path))?),
+        None => None,
+    };
+    let mut baseline = match &args.baseline {
+        Some(path) if !args.write_baseline => {
+            Some(Baseline::load(Path::new(path))?)
+        }
+        _ => None,
+    };
+    let mut baselined = Vec::new();
+    let mut allowlist = match &args.allow {
+        Some(path) if !args.write_allow => {
+            Some(Allowlist::load(Path::new(path))?)
+        }
+        _ => None,
+    };
+    let mut allowed = Vec::new();
+    let mut nallowed = 0;
+
+    // Filter comments before they're stored so that counts are right even if
+    // we don't store them all.
+    let mut tracker = CommentTracker::new();
+    tracker.max_per_label = args.max_stored_per_label;
+    let this_year = timestamp::year_of(SystemTime::now());
+    let mut add = |comment: Comment| {
+        let expired = comment.year.is_some_and(|y| i64::from(y) < this_year);
+        let doc = matches!(
+            comment.style,
+            CommentStyle::Comment(kind) if kind.is_doc()
+        );
+        let wanted = match args.docs {
+            Docs::Include => true,
+            Docs::Exclude => !doc,
+            Docs::Only => doc,
+        };
+        if wanted
+            && (comment.attached || !args.attached_only)
+            && (expired || !args.expired)
+            && (args.labels.is_empty()
+                || args.labels.iter().any(|glob| glob.matches(&comment.label)))
+            && !args
+                .exclude_if_matches
+                .iter()
+                .any(|re| re.is_match(&comment.contents))
+        {
+            if args.write_allow {
+                allowed.push(comment.clone());
+            } else if allowlist.as_mut().is_some_and(|a| a.allows(&comment)) {
+                nallowed += 1;
+                return;
+            }
+            let comments = if args.occurrences {
+                comment.split_occurrences()
+            } else {
+                vec![comment]
+            };
+            for c in comments {
+                if args.write_baseline {
+                    baselined.push(c.clone());
+                } else if baseline.as_mut().is_some_and(|b| b.take(&c)) {
+                    continue;
+                }
+                tracker.add(c);
+            }
+        }
+    };
+
+    let run_start = Instant::now();
+    let mut nwarnings = 0;
+    let mut nmalformed = 0;
+    let mut warn = |error: anyhow::Error| {
+        if error.is::<MalformedComment>() {
+            eprintln!("error: {:#}", error);
+            nmalformed += 1;
+        } else {
+            eprintln!("warn: {:#}", error);
+            nwarnings += 1;
+        }
+    };
+    let stats = if literal {
+        let (comments, warnings, stats) = scan_source(args)?;
+        warnings.into_iter().for_each(&mut warn);
+        comments.into_iter().for_each(&mut add);
+        stats
+    } else if args.staged {
+        let comments = todos::scan_staged(Path::new("."), &args.config)?;
+        comments.into_iter().for_each(&mut add);
+        ScanStats::default()
+    } else {
+        // All the trees go into the same tracker, so the report covers them
+        // all.
+        let mut stats = ScanStats::default();
+        for root in &roots {
+            stats +=
+                todos::scan_with(root, &args.config, |result| match result {
+                    Ok(comment) => add(comment),
+                    Err(error) => warn(error),
+                })
+                .map_err(|error| {
+                    anyhow!("stopping because of --strict-files: {:#}", error)
+                })?;
+        }
+        stats
+    };
+
+    if let Some(path) = args.baseline.as_ref().filter(|_| args.write_baseline) {
+        std::fs::write(path, baseline::serialize(&baselined))
+            .with_context(|| format!("write {:?}", path))?;
+    }
+    if let Some(baseline) = &baseline {
+        let gone = baseline.remaining();
+        if gone > 0 {
+            eprintln!(
+                "{} comment{} in the baseline {} no longer found",
+                gone,
+                if gone == 1 { "" } else { "s" },
+                if gone == 1 { "is" } else { "are" }
+            );
+        }
+    }
+
+    if let Some(path) = args.allow.as_ref().filter(|_| args.write_allow) {
+        std::fs::write(path, allow::serialize(&allowed))
+            .with_context(|| format!("write {:?}", path))?;
+    }
+    if let (Some(path), Some(allowlist)) = (&args.allow, &allowlist) {
+        if nallowed > 0 {
+            eprintln!(
+                "{} comment{} left out because of the allowlist",
+                nallowed,
+                if nallowed == 1 { "" } else { "s" }
+            );
+        }
+        // Only a scan of whole trees could have matched every entry.
+        if !literal && !args.staged {
+            for (line, entry) in allowlist.unmatched() {
+                warn(anyhow!(
+                    "{}:{}: allowlist entry {:?} matches no comment",
+                    path,
+                    line,
+                    entry
+                ));
+            }
+        }
+    }
+
+    if let Some(tags) = &tags {
+        tags.apply(&roots, &mut tracker);
+    }
+
+    let ages = if args.age { Some(label_ages(&tracker)) } else { None };
+    if args.blame {
+        blame_comments(&mut tracker);
+    }
+
+    // Everything that reads the files again (tags, ages, and blame) has been
+    // done, so the paths can now be changed for reporting.
+    if let Some(base) = &args.relative_to {
+        let base = absolute_path(base)?;
+        for c in tracker.comments_by_kind.values_mut().flatten() {
+            let file = absolute_path(Path::new(&c.file))?;
+            c.file = relative_path(&file, &base).display().to_string();
+        }
+    }
+    tracker.sort();
+    let header = args.with_header.then(|| {
+        let roots = if args.stdin || args.eval.is_some() {
+            let default = if args.stdin { "<stdin>" } else { "<eval>" };
+            vec![args.stdin_filename.as_deref().unwrap_or(default).to_string()]
+        } else if args.staged {
+            vec![String::from("<staged>")]
+        } else {
+            args.paths
+                .iter()
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect()
+        };
+        ReportHeader::new(roots, &args.config)
+    });
+    let options = ReportOptions {
+        format: args.format,
+        color: use_color(args),
+        hyperlinks: use_hyperlinks(args)
+            .then(|| args.hyperlink_url.as_deref().unwrap_or("file://{path}")),
+        issue_url: args.issue_url.as_deref(),
+        ages: ages.as_ref(),
+        group_threshold: args.group_threshold,
+        by_extension: args.by_extension,
+        group_by: args.group_by,
+        sort: args.sort,
+        limit: args.limit,
+        count_only: args.count_only,
+        strip: args.strip,
+        goals: &args.goals,
+        warning_labels: &args.annotate_as_warning,
+        header: header.as_ref(),
+    };
+    if let Some(template) = &args.split_output {
+        write_split_reports(template, &tracker, &options)?;
+    } else {
+        let out = stdout;
+        let result = if args.print0_matches {
+            print_print0_matches(out, &tracker)
+        } else {
+            print_report(out, &tracker, &options)
+        };
+        ignore_broken_pipe(
+            result.and_then(|_| out.flush()).context("writing report"),
+        )?;
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = &args.sqlite {
+        sqlite::write_database(path, &tracker)?;
+    }
+
+    if args.stats {
+        eprintln!("files scanned: {}", stats.files_scanned);
+        if args.config.modified_since.is_some() {
+            eprintln!(
+                "files skipped (not modified since --modified-since): {}",
+                stats.files_unmodified
+            );
+        }
+        if args.config.min_filesize.is_some()
+            || args.config.max_filesize.is_some()
+        {
+            eprintln!(
+                "files skipped (size outside --min-filesize/--max-filesize): \
+                 {}",
+                stats.files_wrong_size
+            );
+        }
+        print_stats(&tracker);
+    }
+
+    if args.warn_case_variants {
+        for variants in case_variants(&tracker) {
+            let variants = variants
+                .iter()
+                .map(|(label, count)| format!("{:?} ({})", label, count))
+                .collect::<Vec<_>>()
+                .join(", ");
+            eprintln!("warning: labels differ only by case: {}", variants);
+        }
+    }
+
+    if args.warn_intrafile_dupes {
+        for (file, lines, c) in intrafile_dupes(&tracker) {
+            let lines = lines
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            eprintln!(
+                "warning: {}: comment repeated on lines {}: {:?}",
+                file,
+                lines,
+                c.first_line()
+            );
+        }
+    }
+
+    let total = tracker.total();
+    let mut failed = args.error_if_found && total > 0;
+
+    let forbidden: Vec<(&String, usize)> = args
+        .fail_on
+        .iter()
+        .map(|label| (label, tracker.count(label)))
+        .filter(|&(_, count)| count > 0)
+        .collect();
+    for (label, count) in &forbidden {
+        eprintln!(
+            "{}: {} comment{} (failing because of --fail-on)",
+            label,
+            count,
+            if *count == 1 { "" } else { "s" }
+        );
+    }
+    if !forbidden.is_empty() {
+        failed = true;
+    }
+
+    if let Some(max) = args.max.filter(|max| total > *max) {
+        eprintln!(
+            "{} comment{} found, more than --max {}",
+            total,
+            if total == 1 { "" } else { "s" },
+            max
+        );
+        failed = true;
+    }
+
+    if args.warnings_as_errors && nwarnings > 0 {
+        eprintln!(
+            "{} warning{} (failing because of --warnings-as-errors)",
+            nwarnings,
+            if nwarnings == 1 { "" } else { "s" }
+        );
+        failed = true;
+    }
+
+    let mut issue_time = None;
+    if let Some(repo) = &args.check_issues {
+        if args.require_open_issue {
+            let start = Instant::now();
+            let mut checker = IssueChecker::new(repo);
+            let violations = check_open_issues(&tracker, &mut checker)?;
+            issue_time = Some(start.elapsed());
+            for (file, line, reason) in &violations {
+                eprintln!("{}:{}: {}", file, line, reason);
+            }
+            if !violations.is_empty() {
+                eprintln!(
+                    "{} comment{} without an open issue",
+                    violations.len(),
+                    if violations.len() == 1 { "" } else { "s" }
+                );
+                failed = true;
+            }
+        }
+    }
+
+    if args.no_public_doc_todos {
+        let violations = public_doc_comments(&tracker);
+        for (c, item) in &violations {
+            eprintln!(
+                "{}:{}: {} in doc comment for public {} `{}`",
+                c.file, c.line, c.label, item.kind, item.name
+            );
+        }
+        if !violations.is_empty() {
+            eprintln!(
+                "{} comment{} in public API docs",
+                violations.len(),
+                if violations.len() == 1 { "" } else { "s" }
+            );
+            failed = true;
+        }
+    }
+
+    if args.require_description {
+        let mut violations: Vec<&Comment> = tracker
+            .comments_by_kind
+            .values()
+            .flatten()
+            .filter(|c| !c.has_description())
+            .collect();
+        violations.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        for c in &violations {
+            eprintln!("{}:{}: {} with no description", c.file, c.line, c.label);
+        }
+        if !violations.is_empty() {
+            eprintln!(
+                "{} comment{} without a description",
+                violations.len(),
+                if violations.len() == 1 { "" } else { "s" }
+            );
+            failed = true;
+        }
+    }
+
+    if args.expired {
+        let mut expired: Vec<&Comment> =
+            tracker.comments_by_kind.values().flatten().collect();
+        expired.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        for c in &expired {
+            eprintln!("{}:{}: {} has expired", c.file, c.line, c.label);
+        }
+        if !expired.is_empty() {
+            eprintln!(
+                "{} expired comment{}",
+                expired.len(),
+                if expired.len() == 1 { "" } else { "s" }
+            );
+            failed = true;
+        }
+    }
+
+    if args.fail_over_goal {
+        let over: Vec<(&String, usize, usize)> = args
+            .goals
+            .iter()
+            .map(|(label, &goal)| (label, tracker.count(label), goal))
+            .filter(|&(_, count, goal)| count > goal)
+            .collect();
+        for (label, count, goal) in &over {
+            eprintln!(
+                "{}: {} ({})",
+                label,
+                count,
+                goal_progress(*count, *goal)
+            );
+        }
+        if !over.is_empty() {
+            eprintln!(
+                "{} label{} over goal",
+                over.len(),
+                if over.len() == 1 { "" } else { "s" }
+            );
+            failed = true;
+        }
+    }
+
+    if args.profile_output {
+        print_profile(&stats, issue_time, run_start.elapsed());
+    }
+
+    if nmalformed > 0 {
+        eprintln!(
+            "{} malformed block comment{} (failing because of --strict)",
+            nmalformed,
+            if nmalformed == 1 { "" } else { "s" }
+        );
+        return Ok(EXIT_FOUND);
+    }
+    Ok(if failed { EXIT_FOUND } else { EXIT_OK })
+}
+
+/// Scans and reports like [`scan_and_report()`], then does it again each time
+/// a file that the scan looks at changes, until interrupted
+///
+/// We poll the files' modification times and sizes rather than asking the
+/// operating system to tell us about changes.  One change is often part of a
+/// burst (e.g., saving many files at once), so after noticing one, we wait
+/// for things to settle before scanning again.
+fn watch(args: &Args, stdout: &mut dyn Write) -> Result<u8, anyhow::Error> {
+    use std::io::IsTerminal;
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+    const SETTLE_INTERVAL: Duration = Duration::from_millis(200);
+
+    // Don't print progress messages for each poll.
+    let mut config = args.config.clone();
+    config.progress = false;
+    let snapshot = || {
+        args.paths
+            .iter()
+            .flat_map(|root| todos::source_files(Path::new(root), &config))
+            .map(|path| {
+                let metadata = std::fs::metadata(&path).ok();
+                let version = metadata
+                    .map(|m| (m.modified().ok(), m.len()))
+                    .unwrap_or_default();
+                (path, version)
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+
+    let clear = std::io::stdout().is_terminal();
+    loop {
+        if clear {
+            write!(stdout, "\x1b[2J\x1b[H")?;
+        }
+        let mut last = snapshot();
+        // A scan can fail for reasons that go away by the next change (like a
+        // file being removed partway through it), so report the failure and
+        // keep watching rather than stop.
+        match scan_and_report(args, stdout) {
+            Ok(EXIT_OK) => (),
+            Ok(_) => eprintln!("a check failed (watching for changes)"),
+            Err(error) => {
+                eprintln!("error: {:#} (watching for changes)", error)
+            }
+        }
+
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let now = snapshot();
+            if now != last {
+                last = now;
+                break;
+            }
+        }
+        loop {
+            std::thread::sleep(SETTLE_INTERVAL);
+            let now = snapshot();
+            if now == last {
+                break;
+            }
+            last = now;
+        }
+    }
+}
+
+/// Scans revisions `old` and `new` of the repository in the current directory
+/// and prints the comments added and removed between them
+fn compare(
+    out: &mut dyn Write,
+    old: &str,
+    new: &str,
+    args: &Args,
+) -> Result<(), anyhow::Error> {
+    let repo = Path::new(".");
+    let old_comments = todos::scan_git_ref(repo, old, &args.config)?;
+    let new_comments = todos::scan_git_ref(repo, new, &args.config)?;
+    let diff = diff::diff(old_comments, new_comments);
+
+    let mut counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for c in &diff.added {
+        counts.entry(&c.label).or_default().0 += 1;
+    }
+    for c in &diff.removed {
+        counts.entry(&c.label).or_default().1 += 1;
+    }
+
+    writeln!(out, "comparing {} to {}", old, new)?;
+    for (label, (added, removed)) in &counts {
+        writeln!(
+            out,
+            "{:?}: +{} -{} (net {:+})",
+            label,
+            added,
+            removed,
+            *added as i64 - *removed as i64
+        )?;
+    }
+    writeln!(
+        out,
+        "total: +{} -{} (net {:+})",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.added.len() as i64 - diff.removed.len() as i64
+    )?;
+
+    if args.verbose {
+        for (heading, comments) in
+            [("added", &diff.added), ("removed", &diff.removed)]
+        {
+            if comments.is_empty() {
+                continue;
+            }
+            writeln!(out, "\n{}:", heading)?;
+            for c in comments {
+                writeln!(
+                    out,
+                    "  {}:{}: {}: {}",
+                    c.file,
+                    c.line,
+                    c.label,
+                    c.first_line()
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Options that affect how a report is printed
+#[derive(Clone, Copy)]
+struct ReportOptions<'a> {
+    format: Format,
+    /// (text only) use color
+    color: bool,
+    /// (text only) make locations into links to this URL template
+    hyperlinks: Option<&'a str>,
+    /// (text only) show the URLs of referenced issues, from this template
+    issue_url: Option<&'a str>,
+    /// (text only) the oldest and newest comment for each label
+    ages: Option<&'a BTreeMap<String, LabelAge>>,
+    /// (text only) lump together labels found fewer times than this in the
+    /// summary
+    group_threshold: Option<usize>,
+    /// (text only) also count comments by file extension in the summary
+    by_extension: bool,
+    /// (text only) how to group comments
+    group_by: GroupBy,
+    /// (text only) how to order groups of comments
+    sort: SortBy,
+    /// (text only) maximum number of comments to list in each group
+    limit: Option<usize>,
+    /// (text and json only) print only the counts
+    count_only: bool,
+    /// (text only) show comments without their comment markers
+    strip: bool,
+    /// (text only) target number of comments for some labels
+    goals: &'a BTreeMap<String, usize>,
+    /// (github only) labels to annotate as warnings rather than notices
+    warning_labels: &'a BTreeSet<String>,
+    /// how the report was produced, to include in it
+    header: Option<&'a ReportHeader>,
+}
+
+/// Prints the report for `tracker` as described by `options`
+fn print_report(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+    options: &ReportOptions,
+) -> std::io::Result<()> {
+    let header = options.header;
+    match options.format {
+        Format::Text if options.group_by != GroupBy::Label => {
+            print_text_grouped(out, tracker, options)
+        }
+        Format::Text => print_text(out, tracker, options),
+        Format::Sexp => print_sexp(out, tracker, header),
+        Format::Rst => print_rst(out, tracker, header),
+        Format::HtmlDashboard => print_html_dashboard(out, tracker, header),
+        Format::Table => print_table(out, tracker, header),
+        Format::Yaml => print_yaml(out, tracker, header),
+        Format::GitGrep => print_git_grep(out, tracker),
+        Format::Lines => print_lines(out, tracker),
+        Format::Json if options.count_only => print_json_counts(out, tracker),
+        Format::Json => print_json(out, tracker),
+        Format::Github => print_github(out, tracker, options.warning_labels),
+        Format::SarifFingerprints => print_sarif(out, tracker, header),
+        #[cfg(feature = "protobuf")]
+        Format::Protobuf => {
+            out.write_all(&protobuf::encode_report_delimited(tracker, header))
+        }
+    }
+}
+
+/// Prints `header` as the start of a text or table report
+fn print_text_header(
+    out: &mut dyn Write,
+    header: &ReportHeader,
+) -> std::io::Result<()> {
+    let list = |items: &[String]| {
+        items
+            .iter()
+            .map(|item| format!("{:?}", strip_control(item)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    writeln!(out, "report from todos {}", header.version)?;
+    writeln!(out, "scanned: {}", list(&header.roots))?;
+    writeln!(out, "keywords: {}", list(&header.keywords))?;
+    for (glob, keywords) in &header.scopes {
+        writeln!(
+            out,
+            "keywords for {:?}: {}",
+            strip_control(glob),
+            list(keywords)
+        )?;
+    }
+    if !header.exclude.is_empty() {
+        writeln!(out, "excluding: {}", list(&header.exclude))?;
+    }
+    writeln!(out)
+}
+
+/// Writes a separate report for each label in `tracker` to the file named by
+/// `template`, with "{label}" replaced by the (sanitized) label
+fn write_split_reports(
+    template: &str,
+    tracker: &CommentTracker,
+    options: &ReportOptions,
+) -> Result<(), anyhow::Error> {
+    // Work out all the file names first so that we don't write anything if
+    // two labels would end up in the same file.
+    let mut paths: BTreeMap<String, &str> = BTreeMap::new();
+    for label in tracker.comments_by_kind.keys() {
+        let path = template.replace("{label}", &file_name_for_label(label));
+        if let Some(other) = paths.insert(path.clone(), label) {
+            bail!(
+                "labels {:?} and {:?} would both be written to {:?}",
+                other,
+                label,
+                path
+            );
+        }
+    }
+
+    for (path, label) in paths {
+        let mut one = CommentTracker::new();
+        one.max_per_label = tracker.max_per_label;
+        one.comments_by_kind
+            .insert(label.to_string(), tracker.comments_by_kind[label].clone());
+        if let Some(&n) = tracker.truncated.get(label) {
+            one.truncated.insert(label.to_string(), n);
+        }
+
+        let path = Path::new(&path);
+        if let Some(parent) =
+            path.parent().filter(|p| !p.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("create {:?}", parent.display()))?;
+        }
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(path)
+                .with_context(|| format!("create {:?}", path.display()))?,
+        );
+        let options =
+            ReportOptions { color: false, hyperlinks: None, ..*options };
+        print_report(&mut file, &one, &options)
+            .and_then(|_| file.flush())
+            .with_context(|| format!("write {:?}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Returns a version of `label` that's safe to use as (part of) a file name
+///
+/// Anything other than ASCII letters, digits, "-", "_", and "." becomes "_",
+/// and so does a label that's entirely dots (like "..").
+fn file_name_for_label(label: &str) -> String {
+    if label.chars().all(|c| c == '.') {
+        return "_".repeat(label.len().max(1));
+    }
+    label
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Returns `result`, except that an error from writing to a closed pipe is
+/// treated as success
+fn ignore_broken_pipe(
+    result: Result<(), anyhow::Error>,
+) -> Result<(), anyhow::Error> {
+    match result {
+        Err(error)
+            if error.chain().any(|cause| {
+                cause
+                    .downcast_ref::<std::io::Error>()
+                    .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+            }) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Scans the source given with `--eval` or on stdin, returning the
+/// comments found and any warnings from parsing it
+fn scan_source(
+    args: &Args,
+) -> Result<(Vec<Comment>, Vec<anyhow::Error>, ScanStats), anyhow::Error> {
+    let mut stats = ScanStats::default();
+    let start = Instant::now();
+    let (contents, default_name) = match &args.eval {
+        Some(source) => (source.clone(), "<eval>"),
+        None => (
+            std::io::read_to_string(std::io::stdin()).context("read stdin")?,
+            "<stdin>",
+        ),
+    };
+    stats.files_scanned = 1;
+    stats.read_time = start.elapsed();
+
+    let start = Instant::now();
+    let path =
+        Path::new(args.stdin_filename.as_deref().unwrap_or(default_name));
+    let keywords = args.config.keywords.for_path(path);
+    let mut warnings = Vec::new();
+    let comments = todos::find_comments(
+        &contents,
+        path,
+        keywords,
+        &args.config,
+        &mut warnings,
+    );
+    stats.parse_time = start.elapsed();
+    Ok((comments, warnings, stats))
+}
+
+/// Returns each group of labels that are the same ignoring case, with the
+/// number of comments for each label
+fn case_variants(tracker: &CommentTracker) -> Vec<Vec<(&str, usize)>> {
+    let mut groups: BTreeMap<String, Vec<(&str, usize)>> = BTreeMap::new();
+    for label in tracker.comments_by_kind.keys() {
+        groups
+            .entry(label.to_lowercase())
+            .or_default()
+            .push((label.as_str(), tracker.count(label)));
+    }
+    groups.into_values().filter(|labels| labels.len() > 1).collect()
+}
+
+/// Returns each comment whose text appears more than once in the same file,
+/// with the file and the lines where it appears
+///
+/// A comment with several labels is stored once for each one, so this
+/// considers each location only once.  Only the comments that were stored are
+/// considered (see `--max-stored-per-label`).
+fn intrafile_dupes(
+    tracker: &CommentTracker,
+) -> Vec<(&str, Vec<usize>, &Comment)> {
+    let mut groups: BTreeMap<(&str, &str), (BTreeSet<usize>, &Comment)> =
+        BTreeMap::new();
+    for c in tracker.comments_by_kind.values().flatten() {
+        groups
+            .entry((&c.file, &c.contents))
+            .or_insert_with(|| (BTreeSet::new(), c))
+            .0
+            .insert(c.line);
+    }
+    let mut dupes: Vec<(&str, Vec<usize>, &Comment)> = groups
+        .into_iter()
+        .filter(|(_, (lines, _))| lines.len() > 1)
+        .map(|((file, _), (lines, c))| (file, lines.into_iter().collect(), c))
+        .collect();
+    dupes.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+    dupes
+}
+
+/// Returns `path` as an absolute path, with "." and ".." components resolved
+/// lexically (without following symlinks)
+fn absolute_path(path: &Path) -> Result<PathBuf, anyhow::Error> {
+    let path = std::path::absolute(path)
+        .with_context(|| format!("resolve {:?}", path.display()))?;
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    Ok(resolved)
+}
+
+/// Returns the path that leads from directory `base` to `path` (both
+/// absolute), using ".." as needed
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+    while path_components.peek().is_some()
+        && path_components.peek() == base_components.peek()
+    {
+        path_components.next();
+        base_components.next();
+    }
+    let relative: PathBuf = base_components
+        .map(|_| Component::ParentDir)
+        .chain(path_components)
+        .collect();
+    if relative.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        relative
+    }
+}
+
+/// The oldest and newest comment with some label
+struct LabelAge {
+    oldest: AgedComment,
+    newest: AgedComment,
+}
+
+/// Where a comment is and when it was written
+struct AgedComment {
+    /// seconds since the Unix epoch
+    time: u64,
+    file: String,
+    line: usize,
+}
+
+impl std::fmt::Display for AgedComment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let days = now.saturating_sub(self.time) / (24 * 60 * 60);
+        write!(f, "{}d ({}:{})", days, strip_control(&self.file), self.line)
+    }
+}
+
+/// Finds the oldest and newest stored comment with each label, using
+/// `git blame` to tell when each comment's first line was written
+///
+/// Files that `git blame` can't handle (e.g., because they're not in a git
+/// repository) are skipped with a warning.
+fn label_ages(tracker: &CommentTracker) -> BTreeMap<String, LabelAge> {
+    let mut blamed: BTreeMap<&str, Option<Vec<u64>>> = BTreeMap::new();
+    let mut ages: BTreeMap<String, LabelAge> = BTreeMap::new();
+    for (label, comments) in &tracker.comments_by_kind {
+        for c in comments {
+            let times = blamed.entry(&c.file).or_insert_with(|| {
+                todos::git::blame_times(Path::new(&c.file))
+                    .map_err(|error| {
+                        eprintln!("warn: blame {:?}: {:#}", c.file, error)
+                    })
+                    .ok()
+            });
+            let Some(&time) =
+                times.as_ref().and_then(|times| times.get(c.line - 1))
+            else {
+                continue;
+            };
+
+            let aged =
+                || AgedComment { time, file: c.file.clone(), line: c.line };
+            match ages.get_mut(label) {
+                None => {
+                    ages.insert(
+                        label.clone(),
+                        LabelAge { oldest: aged(), newest: aged() },
+                    );
+                }
+                Some(age) => {
+                    if time < age.oldest.time {
+                        age.oldest = aged();
+                    }
+                    if time > age.newest.time {
+                        age.newest = aged();
+                    }
+                }
+            }
+        }
+    }
+    ages
+}
+
+/// Fills in who last changed each stored comment, using `git blame` (once
+/// per file)
+///
+/// Files outside a git repository are skipped, with one warning for all of
+/// them.  Other files that `git blame` can't handle (e.g., ones that git
+/// doesn't track) are skipped with a warning each.
+fn blame_comments(tracker: &mut CommentTracker) {
+    let mut blamed: BTreeMap<String, Option<Vec<todos::Blame>>> =
+        BTreeMap::new();
+    let mut warned_outside_repo = false;
+    for c in tracker.comments_by_kind.values_mut().flatten() {
+        let lines = blamed.entry(c.file.clone()).or_insert_with(|| {
+            let path = Path::new(&c.file);
+            todos::git::blame(path)
+                .map_err(|error| {
+                    let dir =
+                        path.parent().filter(|d| !d.as_os_str().is_empty());
+                    if todos::git::in_work_tree(dir.unwrap_or(Path::new("."))) {
+                        eprintln!("warn: blame {:?}: {:#}", c.file, error);
+                    } else if !warned_outside_repo {
+                        eprintln!(
+                            "warn: --blame: {:?} is not in a git repository \
+                             (going on without blame information for files \
+                             like it)",
+                            c.file
+                        );
+                        warned_outside_repo = true;
+                    }
+                })
+                .ok()
+        });
+        c.blame =
+            lines.as_ref().and_then(|lines| lines.get(c.line - 1)).cloned();
+    }
+}
+
+/// Prints a table of the time spent in each phase of the run to stderr
+fn print_profile(
+    stats: &ScanStats,
+    issue_time: Option<Duration>,
+    total: Duration,
+) {
+    let mut phases = vec![
+        ("walking", stats.walk_time),
+        ("reading", stats.read_time),
+        ("parsing", stats.parse_time),
+    ];
+    phases.extend(issue_time.map(|t| ("issue lookups", t)));
+    let accounted: Duration = phases.iter().map(|(_, t)| *t).sum();
+    phases.push(("other", total.saturating_sub(accounted)));
+
+    eprintln!("{:<16} {:>10} {:>6}", "PHASE", "SECONDS", "%");
+    for (phase, time) in &phases {
+        eprintln!(
+            "{:<16} {:>10.3} {:>5.1}%",
+            phase,
+            time.as_secs_f64(),
+            100.0 * time.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON)
+        );
     }
+    eprintln!("{:<16} {:>10.3}", "total", total.as_secs_f64());
+}
 
-    let mut tracker = CommentTracker::new();
-    let walker = walkdir::WalkDir::new(&argv[1])
-        .follow_links(false)
-        .same_file_system(false)
-        .into_iter()
-        .filter_entry(|e| {
-            // Skip any "target" directory found at the root.
-            // TODO-cleanup This looks awful.
-            let skip = if e.depth() == 1 {
-                if let Some(name) = e.path().file_name() {
-                    name == "target"
-                } else {
-                    false
+/// Returns each comment found in the docs of a plain `pub` item, along with
+/// that item, sorted by location
+fn public_doc_comments(tracker: &CommentTracker) -> Vec<(&Comment, &Item)> {
+    let mut found: Vec<_> = tracker
+        .comments_by_kind
+        .values()
+        .flatten()
+        .filter_map(|c| c.public_doc_item().map(|item| (c, item)))
+        .collect();
+    found.sort_by(|(a, _), (b, _)| {
+        (&a.file, a.line, &a.label).cmp(&(&b.file, b.line, &b.label))
+    });
+    found
+}
+
+/// Returns a `(file, line, reason)` tuple for each comment that doesn't
+/// reference an open issue
+///
+/// A comment with several labels counts once, and it's fine as long as any of
+/// its labels references an open issue.
+fn check_open_issues<'a>(
+    tracker: &'a CommentTracker,
+    checker: &mut IssueChecker,
+) -> Result<Vec<(&'a str, usize, String)>, anyhow::Error> {
+    let mut issues_by_comment: BTreeMap<_, BTreeSet<u64>> = BTreeMap::new();
+    for comments in tracker.comments_by_kind.values() {
+        for c in comments {
+            let issues =
+                issues_by_comment.entry((c.file.as_str(), c.line)).or_default();
+            issues.extend(c.issue);
+        }
+    }
+
+    let mut violations = Vec::new();
+    for ((file, line), issues) in issues_by_comment {
+        if issues.is_empty() {
+            violations.push((file, line, String::from("no issue reference")));
+            continue;
+        }
+
+        let mut reasons = Vec::new();
+        for issue in issues {
+            match checker.state(issue)? {
+                IssueState::Open => {
+                    reasons.clear();
+                    break;
+                }
+                IssueState::Closed => {
+                    reasons.push(format!("issue #{} is closed", issue))
+                }
+                IssueState::Missing => {
+                    reasons.push(format!("issue #{} does not exist", issue))
                 }
-            } else {
-                false
-            };
-            if skip {
-                eprintln!(
-                    "skipping {:?} (looks like \"target\" directory)",
-                    e.path().display()
-                );
             }
-            !skip
+        }
+        if !reasons.is_empty() {
+            violations.push((file, line, reasons.join(", ")));
+        }
+    }
+
+    Ok(violations)
+}
+
+/// Returns whether the text report should use color
+///
+/// By default, that's only when writing to a terminal, and only if the user
+/// hasn't asked us not to (see <https://no-color.org>).  `--color always`
+/// overrides NO_COLOR.
+fn use_color(args: &Args) -> bool {
+    use std::io::IsTerminal;
+
+    color_wanted(
+        args.color.unwrap_or_default(),
+        std::env::var_os("NO_COLOR"),
+        std::io::stdout().is_terminal(),
+    )
+}
+
+/// Does the work of [`use_color()`], given the value of NO_COLOR (if it's
+/// set) and whether stdout is a terminal
+fn color_wanted(
+    when: When,
+    no_color: Option<OsString>,
+    terminal: bool,
+) -> bool {
+    match when {
+        When::Always => true,
+        When::Never => false,
+        When::Auto => no_color.is_none_or(|v| v.is_empty()) && terminal,
+    }
+}
+
+/// Returns whether the text report should use hyperlinks
+///
+/// There's no reliable way to ask a terminal whether it supports them, but
+/// those that don't generally ignore them, so for "auto" we only rule out
+/// output that isn't a terminal and terminals that say they're dumb.  The
+/// source given with --stdin or --eval has no file to link to.
+fn use_hyperlinks(args: &Args) -> bool {
+    use std::io::IsTerminal;
+
+    match args.hyperlinks.unwrap_or_default() {
+        _ if args.stdin || args.eval.is_some() => false,
+        When::Always => true,
+        When::Never => false,
+        When::Auto => {
+            std::env::var_os("TERM").is_none_or(|term| term != "dumb")
+                && std::io::stdout().is_terminal()
+        }
+    }
+}
+
+/// Returns the URL that a hyperlink to comment `c` points to, given the
+/// `--hyperlink-url` template, or `None` if the file can't be found
+fn hyperlink_url(template: &str, c: &Comment) -> Option<String> {
+    let path = absolute_path(Path::new(&c.file)).ok()?;
+    let (line, column) = c.position();
+    Some(
+        template
+            .replace("{path}", &percent_encode(path.to_str()?))
+            .replace("{line}", &line.to_string())
+            .replace("{column}", &column.to_string()),
+    )
+}
+
+/// Prints the usual human-readable report: every comment we found, grouped by
+/// "kind", followed by a summary of counts
+///
+/// With `color`, each group's heading is printed in bold.  Text that came
+/// from the source tree is stripped of control characters either way, so
+/// that escape sequences in a comment can't mess with the terminal.  With
+/// `hyperlinks`, each comment's location links to that URL template.  With
+/// `ages`, the summary has columns for the oldest and newest comment with each
+/// label.
+fn print_text(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+    options: &ReportOptions,
+) -> std::io::Result<()> {
+    let ReportOptions {
+        color,
+        ages,
+        group_threshold,
+        by_extension,
+        goals,
+        sort,
+        ..
+    } = *options;
+    if let Some(header) = options.header {
+        print_text_header(out, header)?;
+    }
+
+    let (bold, reset) = if color { ("\x1b[1m", "\x1b[0m") } else { ("", "") };
+
+    // Print all the comments that we found, grouped by "kind" (unless we're
+    // only printing the counts).
+    let mut groups: Vec<_> = if options.count_only {
+        Vec::new()
+    } else {
+        tracker.comments_by_kind.iter().collect()
+    };
+    if sort == SortBy::Count {
+        groups.sort_by_key(|(label, _)| Reverse(tracker.count(label)));
+    }
+    for (label, comments) in groups {
+        write!(out, "{}{}", bold, label_count(tracker, label))?;
+        if tracker.truncated.contains_key(label) {
+            write!(out, " (listing only the first {})", comments.len())?;
+        }
+        writeln!(out, "{}", reset)?;
+        print_text_comments(out, comments, tracker.count(label), options)?;
+    }
+
+    // Print a summary of all comments found.  Each row has the count and,
+    // with --age, the oldest and newest comment counted.
+    writeln!(out, "SUMMARY:\n")?;
+    let age_of = |label: &String| {
+        ages.and_then(|ages| ages.get(label))
+            .map(|age| (&age.oldest, &age.newest))
+    };
+    let threshold = group_threshold.unwrap_or(0);
+    // Labels with goals are listed even if none were found.
+    let labels: BTreeSet<&String> =
+        tracker.comments_by_kind.keys().chain(goals.keys()).collect();
+    let (mut shown, grouped): (Vec<&String>, Vec<&String>) =
+        labels.into_iter().partition(|label| {
+            tracker.count(label) >= threshold || goals.contains_key(*label)
         });
+    if sort == SortBy::Count {
+        shown.sort_by_key(|label| Reverse(tracker.count(label)));
+    }
+    let mut rows: Vec<(String, Option<(&AgedComment, &AgedComment)>)> = shown
+        .iter()
+        .map(|label| {
+            let mut counts = label_count(tracker, label);
+            if let Some(&goal) = goals.get(*label) {
+                let progress = goal_progress(tracker.count(label), goal);
+                counts.push_str(&format!(" ({})", progress));
+            }
+            (counts, age_of(label))
+        })
+        .collect();
+    if !grouped.is_empty() {
+        let counts = format!(
+            "comments with {} other label{}: {}",
+            grouped.len(),
+            if grouped.len() == 1 { "" } else { "s" },
+            grouped.iter().map(|label| tracker.count(label)).sum::<usize>()
+        );
+        let grouped_ages: Vec<_> =
+            grouped.iter().filter_map(|label| age_of(label)).collect();
+        let oldest = grouped_ages.iter().map(|a| a.0).min_by_key(|a| a.time);
+        let newest = grouped_ages.iter().map(|a| a.1).max_by_key(|a| a.time);
+        rows.push((counts, oldest.zip(newest)));
+    }
 
-    // Iterate all the found items, invoking do_file() on each one.
-    // Since we want to handle all errors the same way, it's easiest to pass the
-    // Result directly to do_file() and let it return it or some other error.
-    for maybe_entry in walker {
-        if let Err(error) = do_file(&mut tracker, maybe_entry) {
-            eprintln!("warn: {:#}", error);
+    let width = rows.iter().map(|r| r.0.chars().count()).max().unwrap_or(0);
+    let oldest_width = rows
+        .iter()
+        .filter_map(|r| r.1)
+        .map(|(oldest, _)| oldest.to_string().chars().count())
+        .max()
+        .unwrap_or(0);
+    for (counts, age) in rows {
+        match age {
+            Some((oldest, newest)) => writeln!(
+                out,
+                "{:<width$}  oldest: {:<oldest_width$}  newest: {}",
+                counts,
+                oldest.to_string(),
+                newest,
+            )?,
+            None => writeln!(out, "{}", counts)?,
         }
     }
 
-    // Print all the comments that we found, grouped by "kind".
-    for (label, comments) in &tracker.comments_by_kind {
-        println!("comments with \"{}\": {}", label, comments.len());
-        for c in comments {
-            println!(
-                "  found {:?} in file {} line {}",
-                label, c.file, c.location
-            );
-            println!(
-                "{}",
-                c.contents
-                    .lines()
-                    .map(|l| format!("    {}\n", l))
-                    .collect::<Vec<_>>()
-                    .join("")
-            );
+    if by_extension {
+        print_extension_counts(out, tracker)?;
+    }
+
+    print_issue_counts(out, tracker)?;
+    writeln!(out, "total comments found: {}", tracker.total())?;
+    Ok(())
+}
+
+/// Returns the line counting the comments with `label`, which is both the
+/// heading of their listing in the text report and their row in its summary
+///
+/// The count includes comments that weren't stored (see
+/// [`CommentTracker::count()`]).
+fn label_count(tracker: &CommentTracker, label: &str) -> String {
+    format!(
+        "comments with \"{}\": {}",
+        strip_control(label),
+        tracker.count(label)
+    )
+}
+
+/// Prints the text report like [`print_text()`], but with the comments
+/// grouped by file or by assignee (as in "TODO(alice)") rather than by label
+///
+/// Each file's comments are listed in line order.  Comments without an
+/// assignee are listed last, as "(unassigned)".
+fn print_text_grouped(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+    options: &ReportOptions,
+) -> std::io::Result<()> {
+    if let Some(header) = options.header {
+        print_text_header(out, header)?;
+    }
+
+    let (bold, reset) =
+        if options.color { ("\x1b[1m", "\x1b[0m") } else { ("", "") };
+    let comments = tracker.comments_by_kind.values().flatten();
+    // Each group is the start of its heading (and summary line) and its
+    // comments.
+    let mut groups: Vec<(String, Vec<&Comment>)> = match options.group_by {
+        GroupBy::Label => unreachable!("print_text() groups by label"),
+        GroupBy::File => {
+            let mut by_file: BTreeMap<&str, Vec<&Comment>> = BTreeMap::new();
+            for c in comments {
+                by_file.entry(&c.file).or_default().push(c);
+            }
+            by_file
+                .into_iter()
+                .map(|(file, mut comments)| {
+                    comments.sort_by_key(|c| c.position());
+                    let name = format!("in \"{}\"", strip_control(file));
+                    (name, comments)
+                })
+                .collect()
         }
+        GroupBy::Assignee => {
+            let mut assigned: BTreeMap<&str, Vec<&Comment>> = BTreeMap::new();
+            let mut unassigned = Vec::new();
+            for c in comments {
+                match &c.assignee {
+                    Some(name) => assigned.entry(name).or_default().push(c),
+                    None => unassigned.push(c),
+                }
+            }
+            assigned
+                .into_iter()
+                .map(|(name, comments)| {
+                    (format!("for \"{}\"", strip_control(name)), comments)
+                })
+                .chain(
+                    (!unassigned.is_empty()).then(|| {
+                        (String::from("for (unassigned)"), unassigned)
+                    }),
+                )
+                .collect()
+        }
+    };
+
+    if options.sort == SortBy::Count {
+        groups.sort_by_key(|(_, comments)| Reverse(comments.len()));
     }
 
-    // Print a summary of all comments found.
-    let mut total = 0;
-    println!("SUMMARY:\n");
-    for (label, comments) in &tracker.comments_by_kind {
-        println!("comments with \"{}\": {}", label, comments.len());
-        total += comments.len();
+    for (name, comments) in groups.iter().filter(|_| !options.count_only) {
+        writeln!(
+            out,
+            "{}comments {}: {}{}",
+            bold,
+            name,
+            comments.len(),
+            reset
+        )?;
+        print_text_comments(
+            out,
+            comments.iter().copied(),
+            comments.len(),
+            options,
+        )?;
     }
 
-    println!("total comments found: {}", total);
+    writeln!(out, "SUMMARY:\n")?;
+    for (name, comments) in &groups {
+        writeln!(out, "comments {}: {}", name, comments.len())?;
+    }
+    if options.by_extension {
+        print_extension_counts(out, tracker)?;
+    }
+    print_issue_counts(out, tracker)?;
+    writeln!(out, "total comments found: {}", tracker.total())?;
+    Ok(())
+}
 
+/// Prints some of `comments` (all of them, unless there's a limit) for the
+/// text report, noting how many of the `count` comments in the group weren't
+/// printed
+fn print_text_comments<'a>(
+    out: &mut dyn Write,
+    comments: impl IntoIterator<Item = &'a Comment>,
+    count: usize,
+    options: &ReportOptions,
+) -> std::io::Result<()> {
+    let limit = options.limit.unwrap_or(usize::MAX);
+    for c in comments.into_iter().take(limit) {
+        print_text_comment(out, c, options)?;
+    }
+    if count > limit {
+        writeln!(out, "  ... and {} more\n", count - limit)?;
+    }
     Ok(())
 }
 
-/// Process one file, finding all TODO-like comments
-fn do_file(
-    tracker: &mut CommentTracker,
-    maybe_entry: Result<walkdir::DirEntry, walkdir::Error>,
-) -> Result<(), anyhow::Error> {
-    if maybe_entry.is_err() {
-        // walkdir failed to read this entry.
-        return maybe_entry.map(|_| ()).context("walking tree");
+/// Prints one comment in the text report, as found with label `c.label`
+fn print_text_comment(
+    out: &mut dyn Write,
+    c: &Comment,
+    options: &ReportOptions,
+) -> std::io::Result<()> {
+    let (cyan, dim, reset) = if options.color {
+        ("\x1b[36m", "\x1b[2m", "\x1b[0m")
+    } else {
+        ("", "", "")
+    };
+    // The location is given as "file:line:column" (of the label), which
+    // editors and terminals understand, and then the lines of the comment if
+    // there are several.
+    let (line, column) = c.position();
+    let mut location = format!(
+        "{}{}{}{}:{}:{}",
+        cyan,
+        strip_control(&c.file),
+        reset,
+        dim,
+        line,
+        column
+    );
+    if c.end_line > c.line {
+        location.push_str(&format!(" (lines {}-{})", c.line, c.end_line));
     }
+    location.push_str(reset);
+    match options.hyperlinks.and_then(|template| hyperlink_url(template, c)) {
+        Some(url) => write!(
+            out,
+            "  found {:?} in file \x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\",
+            c.label, url, location
+        )?,
+        None => write!(out, "  found {:?} in file {}", c.label, location)?,
+    }
+    if let Some(tag) = &c.tag {
+        write!(out, " [{}]", strip_control(tag))?;
+    }
+    if let Some(blame) = &c.blame {
+        write!(
+            out,
+            " (by {} in {})",
+            strip_control(&blame.author),
+            strip_control(&blame.commit)
+        )?;
+    }
+    match (options.issue_url, c.issue) {
+        (Some(template), Some(issue)) => writeln!(
+            out,
+            " (issue: {})",
+            strip_control(&template.replace("{}", &issue.to_string()))
+        )?,
+        _ => writeln!(out)?,
+    }
+    let lines = if options.strip {
+        c.stripped_lines()
+    } else {
+        c.contents.lines().collect()
+    };
+    let context = |lines: &[String]| {
+        lines
+            .iter()
+            .map(|l| match l.as_str() {
+                "" => String::from("    |\n"),
+                l => format!("    | {}\n", strip_control(l)),
+            })
+            .collect::<Vec<_>>()
+            .join("")
+    };
+    writeln!(
+        out,
+        "{}{}{}",
+        context(&c.context_before),
+        lines
+            .iter()
+            .map(|l| format!("    {}\n", strip_control(l)))
+            .collect::<Vec<_>>()
+            .join(""),
+        context(&c.context_after),
+    )
+}
+
+/// Prints the text report's table of how many comments were found in files
+/// with each extension, most first
+fn print_extension_counts(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+) -> std::io::Result<()> {
+    writeln!(out, "\ncomments by file extension:")?;
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for c in tracker.comments_by_kind.values().flatten() {
+        let extension = Path::new(&c.file)
+            .extension()
+            .map_or(String::from("(none)"), |e| {
+                format!(".{}", strip_control(&e.to_string_lossy()))
+            });
+        *counts.entry(extension).or_default() += 1;
+    }
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let width = counts.iter().map(|c| c.0.chars().count()).max().unwrap_or(0);
+    for (extension, count) in &counts {
+        writeln!(out, "  {:<width$}  {}", extension, count)?;
+    }
+    writeln!(out)
+}
 
-    let entry = maybe_entry.unwrap();
-    let path = entry.path();
+/// Prints the part of `--stats` that's about the comments found: which files
+/// they're in and how they break down by label
+///
+/// Comments that weren't kept (see `--max-stored-per-label`) are counted by
+/// label but not by file.
+fn print_stats(tracker: &CommentTracker) {
+    const TOP_FILES: usize = 10;
 
-    // Skip anything that doesn't end with ".rs".
-    match path.extension() {
-        Some(ext) if ext == "rs" => (),
-        _ => {
-            return Ok(());
+    let mut by_file: Vec<(&str, usize)> =
+        tracker.counts_by_file().into_iter().collect();
+    eprintln!("files with comments: {}", by_file.len());
+    if !by_file.is_empty() {
+        by_file.sort_by_key(|(_, count)| Reverse(*count));
+        eprintln!("files with the most comments:");
+        for (file, count) in by_file.iter().take(TOP_FILES) {
+            eprintln!("  {:>5}  {}", count, strip_control(file));
         }
     }
 
-    // Open the file and then stat it (presumably by fd).  Skip anything that's
-    // not a regular file.
-    let file = std::fs::File::open(entry.path())
-        .with_context(|| format!("open {:?}", path.display()))?;
-    let metadata = file
-        .metadata()
-        .with_context(|| format!("metadata for {:?}", path.display()))?;
-    if !metadata.is_file() {
+    let total = tracker.total();
+    if total == 0 {
+        return;
+    }
+    let width = tracker
+        .comments_by_kind
+        .keys()
+        .map(|label| strip_control(label).chars().count())
+        .max()
+        .unwrap_or(0);
+    eprintln!("share of comments by label:");
+    for label in tracker.comments_by_kind.keys() {
+        let count = tracker.count(label);
+        eprintln!(
+            "  {:<width$}  {:>5}  {:>5.1}%",
+            strip_control(label),
+            count,
+            100.0 * count as f64 / total as f64
+        );
+    }
+}
+
+/// Prints how many comments reference an issue (as in "TODO#123") and how many
+/// don't, for the summary of the text report
+///
+/// Nothing is printed if none do, since then there's probably no such
+/// convention.
+fn print_issue_counts(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+) -> std::io::Result<()> {
+    let (with, without) = tracker.issue_counts();
+    if with == 0 {
         return Ok(());
     }
+    writeln!(
+        out,
+        "comments referencing an issue: {} ({} without)",
+        with, without
+    )
+}
 
-    // Read the file.
-    println!("reading {:?}", path.display());
-    let contents = std::io::read_to_string(&file)
-        .with_context(|| format!("read {:?}", path.display()))?;
+/// Describes how `count` compares with the goal `goal`, as in "goal 30, over
+/// by 12"
+fn goal_progress(count: usize, goal: usize) -> String {
+    match count.cmp(&goal) {
+        Ordering::Greater => format!("goal {}, over by {}", goal, count - goal),
+        Ordering::Equal => format!("goal {}, reached", goal),
+        Ordering::Less => format!("goal {}, under by {}", goal, goal - count),
+    }
+}
 
-    // Pull the TODO-like comments out of the file and track them.
-    let chunker = CommentIterator::new(&contents);
-    for (line, chunk) in chunker {
-        tracker.found_possible_comment(&chunk, path, line);
+/// Returns `s` without any terminal escape sequences or other control
+/// characters (except tabs)
+///
+/// This removes whole sequences like "ESC [ 31 m" and "ESC ] ... BEL" rather
+/// than just the ESC character, so that what's left is the readable text.
+fn strip_control(s: &str) -> Cow<'_, str> {
+    if !s.chars().any(|c| c.is_control() && c != '\t') {
+        return Cow::Borrowed(s);
     }
 
-    Ok(())
+    let mut stripped = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters and intermediates, then a final byte in
+                // "@" through "~".
+                Some('[') => {
+                    while chars
+                        .next()
+                        .is_some_and(|c| !('@'..='~').contains(&c))
+                    {
+                    }
+                }
+                // OSC and friends: up to BEL or ST ("ESC \").
+                Some(']' | 'P' | '^' | '_') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07'
+                            || (c == '\x1b'
+                                && chars.next_if_eq(&'\\').is_some())
+                        {
+                            break;
+                        }
+                    }
+                }
+                // Anything else is a two-character sequence.
+                _ => (),
+            },
+            '\t' => stripped.push(c),
+            c if c.is_control() => (),
+            c => stripped.push(c),
+        }
+    }
+    Cow::Owned(stripped)
 }
 
-/// Represents a particular comment found in a particular file
-struct Comment {
-    contents: String,
-    file: String,
-    location: String,
+/// Prints the comments that we found as an aligned table, one row per
+/// comment, sorted by file and then line
+///
+/// The table is sized to fit the terminal (as given by `$COLUMNS`), falling
+/// back to 80 columns if we're not writing to a terminal.  Comments are cut
+/// down to their first line, and that's truncated if it doesn't fit.  As with
+/// the text report, control characters are stripped.
+fn print_table(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+    header: Option<&ReportHeader>,
+) -> std::io::Result<()> {
+    use std::io::IsTerminal;
+
+    if let Some(header) = header {
+        print_text_header(out, header)?;
+    }
+
+    let width = std::env::var("COLUMNS")
+        .ok()
+        .filter(|_| std::io::stdout().is_terminal())
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(80usize);
+
+    let mut comments: Vec<_> =
+        tracker.comments_by_kind.values().flatten().collect();
+    comments.sort_by(|a, b| {
+        (&a.file, a.line, &a.label).cmp(&(&b.file, b.line, &b.label))
+    });
+    let rows: Vec<[String; 4]> = comments
+        .iter()
+        .map(|c| {
+            let (line, column) = c.position();
+            let line = format!("{}:{}", line, column);
+            [&c.label, &c.file, &line, c.first_line()]
+                .map(|cell| strip_control(cell).replace('\t', " "))
+        })
+        .collect();
+
+    let header = ["Label", "File", "Line", "Comment"].map(String::from);
+    let mut widths = [0; 4];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+
+    // Give the comment whatever's left over after the other columns and the
+    // borders ("| " before each column and " |" at the end).
+    let fixed = widths[..3].iter().sum::<usize>() + 3 * 4 + 1;
+    widths[3] = widths[3].min(width.saturating_sub(fixed).max(10));
+
+    let rule =
+        widths.iter().map(|w| "-".repeat(w + 2)).collect::<Vec<_>>().join("+");
+    let rule = format!("+{}+", rule);
+    let format_row = |row: &[String; 4]| {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (cell, w))| {
+                let cell = truncate(cell, w);
+                if i == 2 {
+                    format!(" {:>w$} ", cell, w = w)
+                } else {
+                    format!(" {:<w$} ", cell, w = w)
+                }
+            })
+            .collect();
+        format!("|{}|", cells.join("|"))
+    };
+
+    writeln!(out, "{}", rule)?;
+    writeln!(out, "{}", format_row(&header))?;
+    writeln!(out, "{}", rule)?;
+    for row in &rows {
+        writeln!(out, "{}", format_row(row))?;
+    }
+    writeln!(out, "{}", rule)
+}
+
+/// Returns `s`, cut down to at most `width` characters (ending with "...") if
+/// it's longer than that
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        return s.to_string();
+    }
+    let kept: String = s.chars().take(width.saturating_sub(3)).collect();
+    format!("{}...", kept)
 }
 
-/// Tracks all TODO-like comments found in our search, grouped by a "kind"
+/// Prints the comments that we found as a self-contained HTML page
 ///
-/// The kind is basically whatever whitespace-separated word we identified as
-/// TODO-like.  This might be "TODO" or "XXX" or "TODO-security" or whatever.
-struct CommentTracker {
-    comments_by_kind: BTreeMap<String, Vec<Comment>>,
+/// The page embeds all of the data as JSON and renders the table and chart
+/// with inline JavaScript, so it needs nothing else (not even a web server).
+fn print_html_dashboard(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+    header: Option<&ReportHeader>,
+) -> std::io::Result<()> {
+    let labels = tracker
+        .comments_by_kind
+        .keys()
+        .map(|label| {
+            format!(
+                "{{\"label\":{},\"count\":{}}}",
+                json::quote(label),
+                tracker.count(label)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let comments = tracker
+        .comments_by_kind
+        .values()
+        .flatten()
+        .map(|c| {
+            let (line, column) = c.position();
+            format!(
+                "{{\"id\":{},\"label\":{},\"file\":{},\"line\":{},\
+                 \"column\":{},\"tag\":{},\"text\":{}}}",
+                json::quote(&c.id),
+                json::quote(&c.label),
+                json::quote(&c.file),
+                line,
+                column,
+                c.tag.as_deref().map_or(String::from("null"), json::quote),
+                json::quote(&c.contents)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let header = header.map_or(String::from("null"), header_json);
+    let data = format!(
+        "{{\"header\":{},\"total\":{},\"labels\":[{}],\"comments\":[{}]}}",
+        header,
+        tracker.total(),
+        labels,
+        comments
+    );
+
+    // "<" only appears inside JSON strings, where "\u003c" means the same
+    // thing.  Escaping it keeps "</script>" in a comment from ending the
+    // script element early.
+    let data = data.replace('<', "\\u003c");
+    write!(out, "{}", DASHBOARD_TEMPLATE.replace("TODOS_DATA", &data))
 }
 
-impl CommentTracker {
-    fn new() -> CommentTracker {
-        CommentTracker { comments_by_kind: BTreeMap::new() }
+/// template for `--format html-dashboard`
+const DASHBOARD_TEMPLATE: &str = include_str!("dashboard.html");
+
+/// Prints the comments that we found as a reStructuredText document
+///
+/// There's a section for each label containing a definition list, with one
+/// entry per comment.  The comment's text is a line block so that its line
+/// breaks survive.  Comments are sorted by file, then where their labels are
+/// (see [`Comment::position()`]).
+fn print_rst(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+    header: Option<&ReportHeader>,
+) -> std::io::Result<()> {
+    let title = "TODO-like comments";
+    let rule = "=".repeat(title.len());
+    writeln!(out, "{}\n{}\n{}\n", rule, title, rule)?;
+
+    // The header is a field list, which Sphinx and docutils show as a table
+    // of metadata.
+    if let Some(header) = header {
+        let list = |items: &[String]| {
+            items
+                .iter()
+                .map(|item| format!("``{}``", rst_literal(item)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        writeln!(out, ":Generated by: todos {}", header.version)?;
+        writeln!(out, ":Scanned: {}", list(&header.roots))?;
+        writeln!(out, ":Keywords: {}", list(&header.keywords))?;
+        for (glob, keywords) in &header.scopes {
+            writeln!(
+                out,
+                ":Keywords for ``{}``: {}",
+                rst_literal(glob),
+                list(keywords)
+            )?;
+        }
+        if !header.exclude.is_empty() {
+            writeln!(out, ":Excluding: {}", list(&header.exclude))?;
+        }
+        writeln!(out)?;
     }
 
-    fn found_possible_comment(
-        &mut self,
-        contents: &str,
-        path: &Path,
-        line: usize,
-    ) {
-        let mut found_kinds = BTreeSet::new();
+    for (label, comments) in &tracker.comments_by_kind {
+        let heading =
+            format!("{} ({})", rst_escape(label), tracker.count(label));
+        writeln!(
+            out,
+            "{}\n{}\n",
+            heading,
+            "-".repeat(heading.chars().count())
+        )?;
 
-        // Figure out what "kinds" of comment this is.  There may be any number
-        // of these.  A comment might have no TODO-like things in it (in which
-        // case we won't track it) or one of them, or more than one (e.g.,
-        // "TODO-security" and "TODO-coverage").  We will track the entire
-        // comment once for each "kind" that we find in it.
-        for word in contents.split_whitespace() {
-            if word.starts_with("XXX")
-                || word.starts_with("FIXME")
-                || word.starts_with("TODO")
-            {
-                let mut label = word;
-                // People use "TODO" and "TODO:" interchangeably.  Treat them
-                // the same.
-                if word.ends_with(':') {
-                    label = &word[0..word.len() - 1];
-                }
-                found_kinds.insert(label);
+        let mut comments: Vec<_> = comments.iter().collect();
+        comments.sort_by(|a, b| {
+            (&a.file, a.position()).cmp(&(&b.file, b.position()))
+        });
+        for c in comments {
+            let (line, column) = c.position();
+            write!(out, "``{}`` line {} column {}", c.file, line, column)?;
+            if let Some(tag) = &c.tag {
+                write!(out, " : {}", rst_escape(tag))?;
             }
+            writeln!(out)?;
+            for line in c.contents.lines() {
+                writeln!(out, "    | {}", rst_escape(line))?;
+            }
+            writeln!(out)?;
         }
+    }
 
-        for k in found_kinds {
-            let comments_for_this_kind = self
-                .comments_by_kind
-                .entry(k.to_string())
-                .or_insert_with(Vec::new);
-            comments_for_this_kind.push(Comment {
-                contents: contents.to_string(),
-                file: path.display().to_string(),
-                location: format!("line {}", line),
-            });
+    Ok(())
+}
+
+/// Returns `s` made suitable for an inline literal (between double
+/// backquotes), which can't contain escapes
+///
+/// Backquotes are replaced with "'" and control characters are stripped;
+/// this is only used for patterns and keywords, which won't normally contain
+/// either.
+fn rst_literal(s: &str) -> String {
+    strip_control(s).replace('`', "'")
+}
+
+/// Escapes characters that reStructuredText would otherwise treat as markup
+fn rst_escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        if "\\`*_|[]<>:".contains(c) {
+            escaped.push('\\');
         }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Prints the comments that we found as one s-expression
+///
+/// The output looks like:
+///
+/// ```text
+/// ((label "TODO"
+///   (comments ((id "..." file "..." line 42 column 8 text "...") ...)))
+///  ...)
+/// ```
+///
+/// The line and column are where the label is (see
+/// [`Comment::position()`]).  Labels are sorted, and so are the comments for
+/// each label (by file, then line and column), so that the output is
+/// deterministic.  With a header, the first
+/// element of the list is `(header version "..." roots (...) ...)` instead.
+fn print_sexp(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+    header: Option<&ReportHeader>,
+) -> std::io::Result<()> {
+    let mut groups = Vec::new();
+    if let Some(header) = header {
+        let list = |items: &[String]| {
+            let items: Vec<String> =
+                items.iter().map(|item| sexp_string(item)).collect();
+            format!("({})", items.join(" "))
+        };
+        let scopes: Vec<String> = header
+            .scopes
+            .iter()
+            .map(|(glob, keywords)| {
+                format!(
+                    "(glob {} keywords {})",
+                    sexp_string(glob),
+                    list(keywords)
+                )
+            })
+            .collect();
+        groups.push(format!(
+            "(header version {} roots {} keywords {} scopes ({}) exclude {})",
+            sexp_string(&header.version),
+            list(&header.roots),
+            list(&header.keywords),
+            scopes.join(" "),
+            list(&header.exclude)
+        ));
+    }
+    for (label, comments) in &tracker.comments_by_kind {
+        let mut comments: Vec<_> = comments.iter().collect();
+        comments.sort_by(|a, b| {
+            (&a.file, a.position()).cmp(&(&b.file, b.position()))
+        });
+        let comments = comments
+            .iter()
+            .map(|c| {
+                let (line, column) = c.position();
+                let tag = match &c.tag {
+                    Some(tag) => format!(" tag {}", sexp_string(tag)),
+                    None => String::new(),
+                };
+                format!(
+                    "(id {} file {} line {} column {} text {}{})",
+                    sexp_string(&c.id),
+                    sexp_string(&c.file),
+                    line,
+                    column,
+                    sexp_string(&c.contents),
+                    tag
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        groups.push(format!(
+            "(label {} (comments ({})))",
+            sexp_string(label),
+            comments
+        ));
     }
+
+    writeln!(out, "({})", groups.join(" "))
 }
 
-/// "Parses" a file (in a very limited sense), emitting the comments found in it
-// It's tempting to use the "syn" crate for this, but it's not that easy to
-// visit all of the non-doc comments in a file.
-struct CommentIterator<'a> {
-    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+/// Quotes `s` as a Lisp string literal
+fn sexp_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
 }
 
-impl<'a> CommentIterator<'a> {
-    pub fn new(input: &'a str) -> CommentIterator {
-        CommentIterator { lines: input.lines().enumerate() }
+/// Prints the comments that we found as a YAML list
+///
+/// The output looks like:
+///
+/// ```text
+/// - label: "TODO"
+///   file: "src/main.rs"
+///   line: 42
+///   column: 8
+///   start_line: 41
+///   end_line: 42
+///   contents: |
+///     // TODO do the thing
+///     // properly
+/// ```
+///
+/// The line and column are where the label is (see
+/// [`Comment::position()`]), and the comment spans lines `start_line` to
+/// `end_line`.  Entries have a `tag` too if one was assigned.  Comments are
+/// sorted by file, then line and column, then label, so that the output is
+/// deterministic.  With a
+/// header, the document is a mapping instead, with the header under `header`
+/// and the list under `comments`.
+fn print_yaml(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+    header: Option<&ReportHeader>,
+) -> std::io::Result<()> {
+    if let Some(header) = header {
+        let list = |items: &[String]| {
+            let items: Vec<String> =
+                items.iter().map(|item| json::quote(item)).collect();
+            format!("[{}]", items.join(", "))
+        };
+        writeln!(out, "header:")?;
+        writeln!(out, "  version: {}", json::quote(&header.version))?;
+        writeln!(out, "  roots: {}", list(&header.roots))?;
+        writeln!(out, "  keywords: {}", list(&header.keywords))?;
+        write!(out, "  scopes:")?;
+        if header.scopes.is_empty() {
+            write!(out, " []")?;
+        }
+        writeln!(out)?;
+        for (glob, keywords) in &header.scopes {
+            writeln!(out, "    - glob: {}", json::quote(glob))?;
+            writeln!(out, "      keywords: {}", list(keywords))?;
+        }
+        writeln!(out, "  exclude: {}", list(&header.exclude))?;
+        write!(out, "comments:")?;
+    }
+
+    let mut comments: Vec<_> =
+        tracker.comments_by_kind.values().flatten().collect();
+    comments.sort_by(|a, b| {
+        (&a.file, a.position(), &a.label).cmp(&(
+            &b.file,
+            b.position(),
+            &b.label,
+        ))
+    });
+    if comments.is_empty() {
+        let space = if header.is_some() { " " } else { "" };
+        return writeln!(out, "{}[]", space);
+    }
+    if header.is_some() {
+        writeln!(out)?;
     }
 
-    fn join(lines: &[&str]) -> String {
-        lines.iter().map(|l| format!("{}\n", l)).collect::<Vec<_>>().join("")
+    for c in comments {
+        writeln!(out, "- label: {}", json::quote(&c.label))?;
+        writeln!(out, "  file: {}", json::quote(&c.file))?;
+        let (line, column) = c.position();
+        writeln!(out, "  line: {}", line)?;
+        writeln!(out, "  column: {}", column)?;
+        writeln!(out, "  start_line: {}", c.line)?;
+        writeln!(out, "  end_line: {}", c.end_line)?;
+        if let Some(tag) = &c.tag {
+            writeln!(out, "  tag: {}", json::quote(tag))?;
+        }
+        if let Some(blame) = &c.blame {
+            writeln!(out, "  author: {}", json::quote(&blame.author))?;
+            writeln!(out, "  commit: {}", json::quote(&blame.commit))?;
+        }
+        write!(out, "  contents:")?;
+        yaml_text(out, &c.contents, "    ")?;
     }
+    Ok(())
 }
 
-impl<'a> Iterator for CommentIterator<'a> {
-    type Item = (usize, String);
+/// Writes `s` as the value of a YAML mapping entry (whose key has just been
+/// written), using a literal block scalar indented by `indent` if we can
+///
+/// A JSON string literal is also a YAML double-quoted scalar, so we fall back
+/// to that for text that a block scalar can't represent as-is: text with no
+/// line breaks, text whose first line starts with a space (which would be
+/// mistaken for indentation), and text with characters that YAML doesn't
+/// allow unescaped or that some parsers treat as line breaks.
+fn yaml_text(
+    out: &mut dyn Write,
+    s: &str,
+    indent: &str,
+) -> std::io::Result<()> {
+    let printable = |c: char| {
+        matches!(c, '\t' | '\n')
+            || (' '..='~').contains(&c)
+            || (('\u{a0}'..='\u{d7ff}').contains(&c)
+                && !matches!(c, '\u{2028}' | '\u{2029}'))
+            || (('\u{e000}'..='\u{fffd}').contains(&c) && c != '\u{feff}')
+            || c >= '\u{10000}'
+    };
+    if !s.contains('\n')
+        || s.starts_with([' ', '\n'])
+        || !s.chars().all(printable)
+    {
+        return writeln!(out, " {}", json::quote(s));
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        /// parser state
-        enum FileState {
-            /// not currently inside a comment
-            NoComment,
-            /// currently inside a line comment
-            InLineComment(usize),
-            /// currently inside a block comment
-            InBlockComment(usize),
+    // The chomping indicator says what to do with the final line break(s):
+    // "-" strips them, "+" keeps all of them, and nothing keeps just one.
+    let body = s.trim_end_matches('\n');
+    let chomp = match s.len() - body.len() {
+        0 => "-",
+        1 => "",
+        _ => "+",
+    };
+    writeln!(out, " |{}", chomp)?;
+    for line in s.strip_suffix('\n').unwrap_or(s).split('\n') {
+        if line.is_empty() {
+            writeln!(out)?;
+        } else {
+            writeln!(out, "{}{}", indent, line)?;
         }
+    }
+    Ok(())
+}
 
-        // Precondition: we are not currently in a comment.
-        let mut state = FileState::NoComment;
+/// Returns `header` as a JSON object
+fn header_json(header: &ReportHeader) -> String {
+    let list = |items: &[String]| {
+        let items: Vec<String> =
+            items.iter().map(|item| json::quote(item)).collect();
+        format!("[{}]", items.join(","))
+    };
+    let scopes: Vec<String> = header
+        .scopes
+        .iter()
+        .map(|(glob, keywords)| {
+            format!(
+                "{{\"glob\":{},\"keywords\":{}}}",
+                json::quote(glob),
+                list(keywords)
+            )
+        })
+        .collect();
+    format!(
+        "{{\"version\":{},\"roots\":{},\"keywords\":{},\
+         \"scopes\":[{}],\"exclude\":{}}}",
+        json::quote(&header.version),
+        list(&header.roots),
+        list(&header.keywords),
+        scopes.join(","),
+        list(&header.exclude)
+    )
+}
 
-        // Keep track of the lines in the current comment.
-        let mut lines = Vec::new();
+/// Prints the report as a SARIF 2.1.0 log, for code scanning tools (e.g.,
+/// GitHub's)
+///
+/// Each comment is a result whose rule is its label.  Its
+/// `partialFingerprints` hold the comment's stable id (see
+/// `todos::stable_id()`), so that a scanning tool can tell it's the same
+/// comment from one commit to the next even after it moves to another line.
+/// With a header, it's under the run's `properties`.
+fn print_sarif(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+    header: Option<&ReportHeader>,
+) -> std::io::Result<()> {
+    let rules: Vec<String> = tracker
+        .comments_by_kind
+        .keys()
+        .map(|label| {
+            format!(
+                "{{\"id\":{},\"shortDescription\":{{\"text\":{}}}}}",
+                json::quote(label),
+                json::quote(&format!("{} comment", label))
+            )
+        })
+        .collect();
 
-        // Read lines until we run out of lines in the file or return early.
-        while let Some((line_numz, raw_line)) = self.lines.next() {
-            let line = raw_line.trim_start().trim_end();
+    let mut comments: Vec<_> =
+        tracker.comments_by_kind.values().flatten().collect();
+    comments.sort_by(|a, b| {
+        (&a.file, a.position(), &a.label).cmp(&(
+            &b.file,
+            b.position(),
+            &b.label,
+        ))
+    });
+    let results: Vec<String> = comments
+        .iter()
+        .map(|c| {
+            let (line, column) = c.position();
+            format!(
+                "{{\"ruleId\":{},\"level\":\"note\",\
+                 \"message\":{{\"text\":{}}},\
+                 \"locations\":[{{\"physicalLocation\":{{\
+                 \"artifactLocation\":{{\"uri\":{}}},\
+                 \"region\":{{\"startLine\":{},\"startColumn\":{}}}}}}}],\
+                 \"partialFingerprints\":{{\"todosCommentId/v1\":{}}}}}",
+                json::quote(&c.label),
+                json::quote(c.first_line()),
+                json::quote(&sarif_uri(&c.file)),
+                line,
+                column,
+                json::quote(&c.id)
+            )
+        })
+        .collect();
 
-            match state {
-                FileState::NoComment => {
-                    if line.starts_with("//") {
-                        // We've found the start of a line comment.
-                        //
-                        // TODO This won't handle comments on the same line as
-                        // source code.  We don't do this often.
-                        lines.push(line);
-                        state = FileState::InLineComment(line_numz + 1);
-                    } else if line.starts_with("/*") && !line.contains("*/") {
-                        // We've found the start of a block comment.
-                        //
-                        // TODO This won't handle nested comments.  We don't do
-                        // this often.
-                        lines.push(line);
-                        state = FileState::InBlockComment(line_numz + 1);
-                    }
+    let properties = match header {
+        Some(header) => {
+            format!(",\"properties\":{{\"header\":{}}}", header_json(header))
+        }
+        None => String::new(),
+    };
+    writeln!(
+        out,
+        "{{\"version\":\"2.1.0\",\
+         \"$schema\":\"https://json.schemastore.org/sarif-2.1.0.json\",\
+         \"runs\":[{{\"tool\":{{\"driver\":{{\"name\":\"todos\",\
+         \"version\":{},\"informationUri\":{},\"rules\":[{}]}}}},\
+         \"results\":[{}]{}}}]}}",
+        json::quote(env!("CARGO_PKG_VERSION")),
+        json::quote(env!("CARGO_PKG_REPOSITORY")),
+        rules.join(","),
+        results.join(","),
+        properties
+    )
+}
 
-                    // We haven't found a comment yet.  Skip this line and
-                    // continue the loop.
-                }
+/// Returns the relative URI that SARIF uses for the file at `path`
+///
+/// A leading "./" is left off, and the rest is percent-encoded.
+fn sarif_uri(mut path: &str) -> String {
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest;
+    }
+    percent_encode(path)
+}
 
-                FileState::InLineComment(start) => {
-                    if !line.starts_with("//") {
-                        // We got to the end of a line comment.  Emit it.
-                        return Some((start, Self::join(&lines)));
-                    } else {
-                        // We're still in a line comment.  Keep reading.
-                        lines.push(line);
-                    }
-                }
+/// Returns `path` with anything other than unreserved characters and "/"
+/// percent-encoded, for use in a URI
+fn percent_encode(path: &str) -> String {
+    let mut uri = String::new();
+    for b in path.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            uri.push(char::from(b));
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+    uri
+}
 
-                FileState::InBlockComment(start) => {
-                    lines.push(line);
-                    if line == "*/" {
-                        // We got to the end of the block comment.  Emit it.
-                        return Some((start, Self::join(&lines)));
-                    }
-                }
-            }
+/// Prints the report as a JSON object mapping each label to a list of its
+/// comments, like:
+///
+/// ```text
+/// {
+///   "TODO": [
+///     {"file": "src/main.rs", "line": 42, "column": 4, "start_line": 42,
+///      "end_line": 42, "contents": "// TODO do it\n"}
+///   ]
+/// }
+/// ```
+///
+/// The line and column are where the label is (see
+/// [`Comment::position()`]), and the comment spans lines `start_line` to
+/// `end_line`.  With `--blame`, comments have an `author` and `commit` too.
+/// Labels are sorted, and each label's comments are sorted by file, then line
+/// and column.  With
+/// no comments at all, this prints `{}`.
+fn print_json(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+) -> std::io::Result<()> {
+    if tracker.comments_by_kind.is_empty() {
+        return writeln!(out, "{{}}");
+    }
+
+    writeln!(out, "{{")?;
+    let nlabels = tracker.comments_by_kind.len();
+    for (i, (label, comments)) in tracker.comments_by_kind.iter().enumerate() {
+        let mut comments: Vec<_> = comments.iter().collect();
+        comments.sort_by(|a, b| {
+            (&a.file, a.position()).cmp(&(&b.file, b.position()))
+        });
+        writeln!(out, "  {}: [", json::quote(label))?;
+        for (j, c) in comments.iter().enumerate() {
+            let (line, column) = c.position();
+            let blame = match &c.blame {
+                Some(blame) => format!(
+                    ", \"author\": {}, \"commit\": {}",
+                    json::quote(&blame.author),
+                    json::quote(&blame.commit)
+                ),
+                None => String::new(),
+            };
+            writeln!(
+                out,
+                "    {{\"file\": {}, \"line\": {}, \"column\": {}, \
+                 \"start_line\": {}, \"end_line\": {}{}, \"contents\": {}}}{}",
+                json::quote(&c.file),
+                line,
+                column,
+                c.line,
+                c.end_line,
+                blame,
+                json::quote(&c.contents),
+                if j + 1 < comments.len() { "," } else { "" }
+            )?;
         }
+        writeln!(out, "  ]{}", if i + 1 < nlabels { "," } else { "" })?;
+    }
+    writeln!(out, "}}")
+}
 
-        match state {
-            FileState::NoComment => {
-                // We got to the end of the file without finding any more
-                // comments.  We ought not to have accumulated any lines.
-                assert_eq!(lines.len(), 0);
-                None
-            }
+/// Prints the number of comments with each label as a JSON object, like
+/// `{"FIXME": 3, "TODO": 42}`, for --count-only
+fn print_json_counts(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+) -> std::io::Result<()> {
+    let counts: Vec<String> = tracker
+        .comments_by_kind
+        .keys()
+        .map(|label| {
+            format!("{}: {}", json::quote(label), tracker.count(label))
+        })
+        .collect();
+    writeln!(out, "{{{}}}", counts.join(", "))
+}
 
-            FileState::InLineComment(start) => {
-                // TODO include filename
-                eprintln!("warning: file ended with a line comment");
-                Some((start, Self::join(&lines)))
+/// Prints each line of each comment that contains its label, the way
+/// `git grep -n` would: "FILE:LINE:TEXT"
+///
+/// We only keep the trimmed text of each comment, so to print lines exactly
+/// as they appear in the file (with their indentation), we read them again.
+/// If that doesn't work (e.g., the source came from stdin), we print what we
+/// have.  A leading "./" is left off of file names, as `git grep` does.  Lines
+/// are sorted by file, then line, and each is printed once even if it has
+/// several labels.
+///
+/// A comment that describes a single occurrence of its label (see
+/// `--occurrences`) is printed as "FILE:LINE:COLUMN:TEXT" instead, like `git
+/// grep -n --column`.
+fn print_git_grep(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+) -> std::io::Result<()> {
+    let mut lines: BTreeMap<(&str, usize, Option<usize>), (&str, &str)> =
+        BTreeMap::new();
+    for c in tracker.comments_by_kind.values().flatten() {
+        let label: Vec<&str> = c.label.split_whitespace().collect();
+        let mut matched = Vec::new();
+        for (i, text) in c.contents.lines().enumerate() {
+            let words: Vec<&str> = text
+                .split_whitespace()
+                .map(|w| w.trim_end_matches([':', ',', '.', ';', '!', '?']))
+                .collect();
+            let raw_words: Vec<&str> = text.split_whitespace().collect();
+            if words.windows(label.len()).any(|w| w == label)
+                || raw_words.windows(label.len()).any(|w| w == label)
+            {
+                matched.push((i, text));
+            }
+        }
+        let first = c.contents.lines().next().unwrap_or("");
+        if c.column.is_some() {
+            // The comment's line is that of the occurrence, not where the
+            // comment starts, so we don't know which of its lines this is.
+            // Without the source, we can only guess the first one that
+            // mentions the label.
+            let text = matched.first().map_or(first, |(_, text)| text);
+            lines.insert((&c.file, c.line, c.column), (text, label[0]));
+        } else if matched.is_empty() {
+            // A phrase can be split across lines.  Report where the comment
+            // starts.
+            lines.insert((&c.file, c.line, None), (first, label[0]));
+        } else {
+            for (i, text) in matched {
+                lines.insert((&c.file, c.line + i, None), (text, label[0]));
             }
+        }
+    }
 
-            FileState::InBlockComment(start) => {
-                // TODO include filename
-                eprintln!("error: file ended with a line comment");
-                Some((start, Self::join(&lines)))
+    let mut sources: BTreeMap<&str, Option<Vec<String>>> = BTreeMap::new();
+    for ((file, line, column), (text, word)) in lines {
+        let source = sources.entry(file).or_insert_with(|| {
+            std::fs::read_to_string(file).ok().map(|s| {
+                todos::source_lines(&s).into_iter().map(String::from).collect()
+            })
+        });
+        let text = source
+            .as_ref()
+            .and_then(|source| source.get(line - 1))
+            .filter(|original| match column {
+                Some(column) => original
+                    .chars()
+                    .skip(column - 1)
+                    .collect::<String>()
+                    .starts_with(word),
+                None => original.trim() == text,
+            })
+            .map_or(text, String::as_str);
+        let mut file = file;
+        while let Some(rest) = file.strip_prefix("./") {
+            file = rest;
+        }
+        match column {
+            Some(column) => {
+                writeln!(out, "{}:{}:{}:{}", file, line, column, text)?
             }
+            None => writeln!(out, "{}:{}:{}", file, line, text)?,
+        }
+    }
+    Ok(())
+}
+
+/// Returns each comment with each of its labels, sorted by file, position (see
+/// [`Comment::position()`]), label, and first line (without its comment
+/// marker) so that output built from them is deterministic and lists the
+/// labels in the order they appear in each file
+fn match_records(tracker: &CommentTracker) -> Vec<(&str, &Comment)> {
+    let mut records: Vec<(&str, &Comment)> = tracker
+        .comments_by_kind
+        .iter()
+        .flat_map(|(label, comments)| {
+            comments.iter().map(move |c| (label.as_str(), c))
+        })
+        .collect();
+    records.sort_by(|(a_label, a), (b_label, b)| {
+        (&a.file, a.position(), a_label, a.first_line()).cmp(&(
+            &b.file,
+            b.position(),
+            b_label,
+            b.first_line(),
+        ))
+    });
+    records
+}
+
+/// Prints one "file:line:column: label: first line" line per comment, giving
+/// where its label is (see [`Comment::position()`])
+///
+/// This is the format that Vim's quickfix list and Emacs's compilation-mode
+/// understand.  A comment with several labels is listed once for each.
+fn print_lines(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+) -> std::io::Result<()> {
+    for (label, c) in match_records(tracker) {
+        let (line, column) = c.position();
+        writeln!(
+            out,
+            "{}:{}:{}: {}: {}",
+            c.file,
+            line,
+            column,
+            label,
+            strip_control(c.first_line())
+        )?;
+    }
+    Ok(())
+}
+
+/// Prints a GitHub Actions workflow command for each comment and label, like
+/// "::notice file=src/main.rs,line=42,col=8::TODO: do it", so that each shows
+/// up as an annotation where its label is
+///
+/// Comments with a label in `warning_labels` are annotated with "::warning"
+/// instead.  A comment with several labels gets an annotation for each.
+/// Values are escaped as the runner expects: "%", "\r", and "\n" everywhere,
+/// and ":" and "," in the file name too.
+fn print_github(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+    warning_labels: &BTreeSet<String>,
+) -> std::io::Result<()> {
+    let escape = |s: &str| {
+        s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+    };
+    for (label, c) in match_records(tracker) {
+        let command =
+            if warning_labels.contains(label) { "warning" } else { "notice" };
+        let file = c.file.strip_prefix("./").unwrap_or(&c.file);
+        let file = escape(file).replace(':', "%3A").replace(',', "%2C");
+        let (line, column) = c.position();
+        writeln!(
+            out,
+            "::{} file={},line={},col={}::{}: {}",
+            command,
+            file,
+            line,
+            column,
+            escape(label),
+            escape(&strip_control(c.first_line()))
+        )?;
+    }
+    Ok(())
+}
+
+/// Prints one NUL-terminated "file:line:column:label:first line" record per
+/// match, giving where its label is (see [`Comment::position()`]), as
+/// [`print_lines()`] does
+///
+/// This is intended for feeding interactive pickers (e.g., `fzf --read0`).
+/// Records are in the order that [`match_records()`] puts them in.
+fn print_print0_matches(
+    out: &mut dyn Write,
+    tracker: &CommentTracker,
+) -> std::io::Result<()> {
+    for (label, c) in match_records(tracker) {
+        let (line, column) = c.position();
+        write!(
+            out,
+            "{}:{}:{}:{}:{}\0",
+            c.file,
+            line,
+            column,
+            label,
+            c.first_line()
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a tracker with the comments found in `src`, as the contents of
+    /// a Rust file called "a.rs", with the default configuration
+    fn tracker_for(src: &str) -> CommentTracker {
+        let mut tracker = CommentTracker::new();
+        for c in todos::scan_string(src, "a.rs", &Config::default()) {
+            tracker.add(c);
+        }
+        tracker
+    }
+
+    /// Returns what `print` writes as a string
+    fn output(
+        print: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+    ) -> String {
+        let mut out = Vec::new();
+        print(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    static NO_GOALS: BTreeMap<String, usize> = BTreeMap::new();
+    static NO_LABELS: BTreeSet<String> = BTreeSet::new();
+
+    /// Returns the options for a plain text report
+    fn text_options() -> ReportOptions<'static> {
+        ReportOptions {
+            format: Format::Text,
+            color: false,
+            hyperlinks: None,
+            issue_url: None,
+            ages: None,
+            group_threshold: None,
+            by_extension: false,
+            group_by: GroupBy::Label,
+            sort: SortBy::Name,
+            limit: None,
+            count_only: false,
+            strip: false,
+            goals: &NO_GOALS,
+            warning_labels: &NO_LABELS,
+            header: None,
         }
     }
+
+    #[test]
+    fn strip_control_removes_whole_sequences() {
+        assert!(matches!(strip_control("plain\ttext"), Cow::Borrowed(_)));
+        assert_eq!(strip_control("\x1b[31;1mred\x1b[0m"), "red");
+        assert_eq!(
+            strip_control("\x1b]8;;file:///x\x07link\x1b]8;;\x1b\\"),
+            "link"
+        );
+        assert_eq!(strip_control("a\x1b]0;title\x1b\\b"), "ab");
+        assert_eq!(strip_control("bell\x07 and\r cr"), "bell and cr");
+        assert_eq!(strip_control("tab\tkept"), "tab\tkept");
+    }
+
+    #[test]
+    fn text_report_strips_escape_sequences() {
+        let tracker = tracker_for(
+            "// TODO \x1b[2J\x1b[Hclear the screen\n\
+             // \x1b]8;;http://evil\x07click\x1b]8;;\x07 me\n",
+        );
+        let out = output(|out| print_text(out, &tracker, &text_options()));
+        assert!(!out.contains('\x1b'));
+        assert!(!out.contains('\x07'));
+        let lines = "    // TODO clear the screen\n    // click me\n";
+        assert!(out.contains(lines));
+    }
+
+    #[test]
+    fn no_color_is_honored() {
+        let set = || Some(OsString::from("1"));
+        assert!(color_wanted(When::Auto, None, true));
+        assert!(!color_wanted(When::Auto, None, false));
+        assert!(!color_wanted(When::Auto, set(), true));
+        // An empty NO_COLOR doesn't count.
+        assert!(color_wanted(When::Auto, Some(OsString::new()), true));
+        assert!(color_wanted(When::Always, set(), false));
+        assert!(!color_wanted(When::Never, None, true));
+    }
+
+    #[test]
+    fn listing_and_summary_share_count_lines() {
+        let tracker = tracker_for(
+            "// TODO-security TODO-coverage check the input\n\
+             fn f() {}\n\
+             // TODO-security again\n",
+        );
+        let out = output(|out| print_text(out, &tracker, &text_options()));
+        let (listing, summary) = out.split_once("SUMMARY:\n").unwrap();
+        let expected = [
+            "comments with \"TODO-coverage\": 1",
+            "comments with \"TODO-security\": 2",
+        ];
+        assert_eq!(label_count(&tracker, "TODO-security"), expected[1]);
+        let headings: Vec<&str> = listing
+            .lines()
+            .filter(|l| l.starts_with("comments with"))
+            .collect();
+        assert_eq!(headings, expected);
+        assert_eq!(
+            summary.lines().filter(|l| !l.is_empty()).collect::<Vec<_>>(),
+            [expected[0], expected[1], "total comments found: 2"]
+        );
+    }
+
+    /// a block comment whose opening "/*" is on a line of its own
+    const BARE_OPENER: &str = "/*\n * XXX: handle errors\n */\nfn f() {}\n";
+
+    #[test]
+    fn github_message_skips_bare_block_opener() {
+        let tracker = tracker_for(BARE_OPENER);
+        let warnings = BTreeSet::from([String::from("XXX")]);
+        let out = output(|out| print_github(out, &tracker, &warnings));
+        assert_eq!(
+            out,
+            "::warning file=a.rs,line=2,col=4::XXX: XXX: handle errors\n"
+        );
+    }
+
+    #[test]
+    fn match_records_skip_bare_block_opener() {
+        let tracker = tracker_for(BARE_OPENER);
+        let out = output(|out| print_print0_matches(out, &tracker));
+        assert_eq!(out, "a.rs:2:4:XXX:XXX: handle errors\0");
+        let out = output(|out| print_lines(out, &tracker));
+        assert_eq!(out, "a.rs:2:4: XXX: XXX: handle errors\n");
+    }
+
+    #[test]
+    fn structured_formats_give_label_position() {
+        let tracker = tracker_for(BARE_OPENER);
+        let out = output(|out| print_json(out, &tracker));
+        assert!(out.contains(
+            "\"line\": 2, \"column\": 4, \"start_line\": 1, \"end_line\": 3"
+        ));
+        let out = output(|out| print_sexp(out, &tracker, None));
+        assert!(out.contains(" line 2 column 4 "));
+    }
+
+    #[test]
+    fn match_records_are_in_position_order() {
+        // The labels on line 3 are out of order by name.
+        let src = "\
+/*
+ * TODO: first
+ */ fn g() {} // TODO-security: one, TODO-coverage: two
+";
+        let tracker = tracker_for(src);
+        let out = output(|out| print_lines(out, &tracker));
+        let text = "TODO-security: one, TODO-coverage: two";
+        assert_eq!(
+            out,
+            format!(
+                "a.rs:2:4: TODO: TODO: first\n\
+                 a.rs:3:18: TODO-security: {}\n\
+                 a.rs:3:38: TODO-coverage: {}\n",
+                text, text
+            )
+        );
+    }
 }
@@ -0,0 +1,80 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Finding unchecked checklist items in Markdown
+//!
+//! Prose has no comments, so in Markdown files we look at task list items
+//! instead:
+//!
+//! ```text
+//! - [ ] TODO: write the section on upgrades
+//! ```
+//!
+//! Only unchecked items count, since a checked one (`- [x]`) is done.  Items
+//! inside fenced code blocks are ignored, since those are usually examples.
+
+/// An unchecked checklist item found by [`checklist_items()`]
+#[derive(Clone, Debug)]
+pub struct ChecklistItem {
+    /// line number (starting from 1) of the item
+    pub line: usize,
+    /// the item's line, trimmed
+    pub contents: String,
+    /// how many characters of indentation precede the item
+    pub indent: usize,
+}
+
+/// Returns the unchecked checklist items in the Markdown document `input`
+pub fn checklist_items(input: &str) -> Vec<ChecklistItem> {
+    let mut items = Vec::new();
+    let mut fence: Option<&str> = None;
+    for (i, raw_line) in crate::source_lines(input).into_iter().enumerate() {
+        let line = raw_line.trim();
+
+        // Skip fenced code blocks.  A fence is closed by another fence made
+        // of the same character.
+        if let Some(open) = fence {
+            if line.starts_with(open) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(open) =
+            ["```", "~~~"].into_iter().find(|f| line.starts_with(f))
+        {
+            fence = Some(open);
+            continue;
+        }
+
+        if unchecked_item(line) {
+            items.push(ChecklistItem {
+                line: i + 1,
+                contents: line.to_string(),
+                indent: raw_line.chars().count()
+                    - raw_line.trim_start().chars().count(),
+            });
+        }
+    }
+    items
+}
+
+/// Returns whether `line` (trimmed) is an unchecked task list item, like
+/// "- [ ] ..." or "1. [ ] ..."
+fn unchecked_item(line: &str) -> bool {
+    let rest = if let Some(rest) = line.strip_prefix(['-', '*', '+']) {
+        rest
+    } else {
+        let ndigits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+        if ndigits == 0 {
+            return false;
+        }
+        match line[ndigits..].strip_prefix(['.', ')']) {
+            Some(rest) => rest,
+            None => return false,
+        }
+    };
+    rest.strip_prefix([' ', '\t']).map(str::trim_start).is_some_and(|rest| {
+        rest == "[ ]" || rest.starts_with("[ ] ") || rest.starts_with("[ ]\t")
+    })
+}
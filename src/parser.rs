@@ -0,0 +1,1173 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Extracting comments from Rust source (or, less carefully, from source in
+//! other languages; see [`crate::lang`])
+
+use crate::lang::Syntax;
+
+/// A comment found in a file by [`CommentIterator`]
+#[derive(Clone, Debug)]
+pub struct RawComment {
+    /// line number (starting from 1) where the comment starts
+    pub line: usize,
+    /// line number (starting from 1) where the comment ends
+    pub end_line: usize,
+    /// text of the comment (one trimmed line of source per line)
+    pub contents: String,
+    /// how many characters of indentation precede each line of `contents`
+    /// in the source
+    pub indents: Vec<usize>,
+    /// the item (function, struct, etc.) that immediately follows the
+    /// comment, if any.  For a doc comment, this is the item it documents.
+    pub item: Option<Item>,
+    /// the innermost item that the comment appears inside of, if any (e.g.,
+    /// "fn main"), judged by indentation
+    pub enclosing: Option<String>,
+    /// whether the comment is followed directly by code, rather than by a
+    /// blank line, another comment, the end of a block, or the end of the
+    /// file
+    pub attached: bool,
+}
+
+/// Describes a Rust item (function, struct, etc.) found in the source
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Item {
+    /// what sort of item this is (e.g., "fn" or "struct")
+    pub kind: String,
+    /// the item's name (for `impl` blocks, roughly what's being implemented,
+    /// like "Iterator for Foo", without any generic parameters)
+    pub name: String,
+    /// whether the item is declared plain `pub` (and not, say, `pub(crate)`)
+    pub public: bool,
+}
+
+/// A problem noticed by [`CommentIterator`] that didn't stop it from
+/// continuing
+#[derive(Clone, Debug)]
+pub struct ParseWarning {
+    /// line number (starting from 1) that the problem relates to
+    pub line: usize,
+    pub message: String,
+}
+
+/// "Parses" a file (in a very limited sense), emitting the comments found in it
+// It's tempting to use the "syn" crate for this, but it's not that easy to
+// visit all of the non-doc comments in a file.
+pub struct CommentIterator<'a> {
+    lines: Vec<&'a str>,
+    /// index into `lines` of the next line to look at
+    next: usize,
+    /// where in that line to pick up, if a block comment ended partway
+    /// through it
+    resume: Option<usize>,
+    /// the string literal that the last line of code ended inside of, if any
+    open_string: Option<OpenString>,
+    /// the line number (starting from 1) where the block comment that the
+    /// last line of code ended inside of started, and how deeply nested we
+    /// are in it, if any
+    open_block: Option<(usize, usize)>,
+    /// end a run of line comments at a line that's less indented than the
+    /// run's first line
+    split_on_dedent: bool,
+    /// keep comments' lines as they are in the source, rather than trimming
+    /// the whitespace around them
+    preserve_indent: bool,
+    /// what comments look like
+    syntax: Syntax,
+    /// items that we're (probably) inside of, with the indentation of the
+    /// line that declared each one, outermost first
+    scopes: Vec<(usize, String)>,
+    /// problems noticed so far
+    warnings: Vec<ParseWarning>,
+}
+
+impl<'a> CommentIterator<'a> {
+    pub fn new(input: &'a str) -> CommentIterator<'a> {
+        CommentIterator {
+            lines: source_lines(input),
+            next: 0,
+            resume: None,
+            open_string: None,
+            open_block: None,
+            split_on_dedent: false,
+            preserve_indent: false,
+            syntax: Syntax::RUST,
+            scopes: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Configures whether a run of `//` lines is split into separate comments
+    /// where a line is less indented than the first line of the run
+    ///
+    /// This keeps a comment at the end of an indented block from being joined
+    /// with one that follows it in the enclosing scope.
+    pub fn split_on_dedent(mut self, split: bool) -> CommentIterator<'a> {
+        self.split_on_dedent = split;
+        self
+    }
+
+    /// Configures whether comments' lines keep the whitespace around them
+    ///
+    /// By default, each line is trimmed.  With this, a line keeps whatever
+    /// follows it, and the lines after a comment's first keep as much of
+    /// their indentation as lines them up with it, so that an aligned table
+    /// or a diagram comes out as it looks in the source.
+    pub fn preserve_indent(mut self, preserve: bool) -> CommentIterator<'a> {
+        self.preserve_indent = preserve;
+        self
+    }
+
+    /// Configures the comment syntax of the source (by default, Rust's)
+    ///
+    /// Items are only tracked in Rust source.
+    pub fn syntax(mut self, syntax: Syntax) -> CommentIterator<'a> {
+        self.syntax = syntax;
+        self
+    }
+
+    /// Returns the problems noticed so far (a block comment that runs to the
+    /// end of the file, or, in Rust, a "*/" that doesn't end one)
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    fn join(lines: &[&str]) -> String {
+        lines.iter().map(|l| format!("{}\n", l)).collect::<Vec<_>>().join("")
+    }
+
+    /// Builds the `RawComment` for a comment starting on line `start` whose
+    /// text is `lines` (each a slice of the corresponding line of source),
+    /// given that the code after it starts at index `self.next`
+    fn emit(&self, start: usize, lines: &[&str]) -> RawComment {
+        let indent = self.lines.get(start - 1).map_or(0, |l| indentation(l));
+        RawComment {
+            line: start,
+            end_line: start + lines.len().max(1) - 1,
+            contents: Self::join(lines),
+            indents: lines
+                .iter()
+                .enumerate()
+                .map(|(i, text)| {
+                    self.lines.get(start - 1 + i).map_or(0, |l| {
+                        let offset =
+                            text.as_ptr() as usize - l.as_ptr() as usize;
+                        l.get(..offset).map_or(0, |code| code.chars().count())
+                    })
+                })
+                .collect(),
+            item: self.item_at(self.next),
+            enclosing: self.enclosing_at(indent),
+            attached: self
+                .lines
+                .get(self.next)
+                .is_some_and(|line| self.starts_with_code(line)),
+        }
+    }
+
+    /// Returns whether `text` (a line, or what follows a block comment on
+    /// one) starts with code, rather than with a comment or the end of a
+    /// block
+    fn starts_with_code(&self, text: &str) -> bool {
+        let text = text.trim();
+        !text.is_empty()
+            && !text.starts_with(self.syntax.line_comment)
+            && !self
+                .syntax
+                .block_comment
+                .is_some_and(|(open, _)| text.starts_with(open))
+            && !text.starts_with('}')
+    }
+
+    /// Emits the block comment whose last line is `lines`' last, which ends
+    /// at byte `end` of that line, arranging to look at the rest of the line
+    /// next time if there's anything there
+    fn emit_block(
+        &mut self,
+        start: usize,
+        lines: &[&str],
+        end: usize,
+    ) -> RawComment {
+        let line = lines[lines.len() - 1];
+        let raw_line = self.lines[self.next];
+        let rest = &line[end..];
+        if rest.trim().is_empty() {
+            self.next += 1;
+            return self.emit(start, lines);
+        }
+
+        let mut lines = lines.to_vec();
+        *lines.last_mut().unwrap() = &line[..end];
+        let mut raw = self.emit(start, &lines);
+        raw.attached = self.starts_with_code(rest);
+        self.resume = Some(rest.as_ptr() as usize - raw_line.as_ptr() as usize);
+        raw
+    }
+
+    /// Returns the part of `raw_line` to keep as a line of a comment, given
+    /// `line`, the trimmed part of it that the comment is in, and for a line
+    /// after the comment's first, that first line, which is line `start`
+    ///
+    /// That's just `line` unless we're preserving indentation (see
+    /// [`CommentIterator::preserve_indent()`]).
+    fn kept(
+        &self,
+        raw_line: &'a str,
+        line: &'a str,
+        first: Option<(usize, &str)>,
+    ) -> &'a str {
+        if !self.preserve_indent {
+            return line;
+        }
+        let mut offset = line.as_ptr() as usize - raw_line.as_ptr() as usize;
+        if let Some((start, first)) = first {
+            // Both of these are within the whitespace that `line` was trimmed
+            // of, so either is a char boundary.
+            let column = first.as_ptr() as usize
+                - self.lines[start - 1].as_ptr() as usize;
+            offset = offset.min(column);
+        }
+        &raw_line[offset..]
+    }
+
+    /// Returns the innermost item that a line with indentation `indent` is
+    /// inside of
+    fn enclosing_at(&self, indent: usize) -> Option<String> {
+        self.scopes
+            .iter()
+            .rev()
+            .find(|(scope_indent, _)| *scope_indent < indent)
+            .map(|(_, name)| name.clone())
+    }
+
+    /// Keeps track of which items we're inside of, given a line of code
+    ///
+    /// We don't parse the code, so this is a guess based on indentation: a
+    /// line ends any items declared at the same or a deeper indentation.
+    fn track_scope(&mut self, raw_line: &str) {
+        let line = raw_line.trim();
+        if !self.syntax.rust
+            || line.is_empty()
+            || line.starts_with('}')
+            || line.starts_with('#')
+        {
+            return;
+        }
+        let indent = indentation(raw_line);
+        while self.scopes.last().is_some_and(|(i, _)| *i >= indent) {
+            self.scopes.pop();
+        }
+        if let Some(item) = parse_item(line) {
+            let name = if item.name.is_empty() {
+                item.kind
+            } else {
+                format!("{} {}", item.kind, item.name)
+            };
+            self.scopes.push((indent, name));
+        }
+    }
+
+    /// Returns the item declared starting at line index `index`, skipping past
+    /// any blank lines and attributes
+    fn item_at(&self, mut index: usize) -> Option<Item> {
+        if !self.syntax.rust {
+            return None;
+        }
+        while let Some(line) = self.lines.get(index) {
+            let line = line.trim();
+            if line.is_empty() {
+                index += 1;
+            } else if line.starts_with("#[") {
+                // Skip the attribute, which may span several lines.
+                while self
+                    .lines
+                    .get(index)
+                    .is_some_and(|l| !l.trim().ends_with(']'))
+                {
+                    index += 1;
+                }
+                index += 1;
+            } else {
+                return parse_item(line);
+            }
+        }
+
+        None
+    }
+}
+
+/// Splits `input` into lines, without their line endings
+///
+/// Unlike `str::lines()`, this also takes the "\r" off a last line that ends
+/// with one but no "\n", so that a file with CRLF line endings yields exactly
+/// the same lines as one with LF line endings, however it ends.
+pub fn source_lines(input: &str) -> Vec<&str> {
+    input.lines().map(|line| line.strip_suffix('\r').unwrap_or(line)).collect()
+}
+
+/// Returns the width of the leading whitespace in `line`
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Parses the start of an item declaration like "pub async fn foo(...)"
+fn parse_item(line: &str) -> Option<Item> {
+    const KINDS: &[&str] = &[
+        "fn",
+        "struct",
+        "enum",
+        "trait",
+        "type",
+        "mod",
+        "const",
+        "static",
+        "union",
+        "impl",
+        "macro_rules!",
+    ];
+    const QUALIFIERS: &[&str] = &["async", "unsafe", "extern", "default"];
+
+    let mut words = line
+        .split(|c: char| c.is_whitespace() || c == '(' || c == '<' || c == ':')
+        .filter(|w| !w.is_empty())
+        .peekable();
+
+    // Visibility comes first.  "pub(crate)" and the like get split into "pub"
+    // followed by "crate)" here.
+    let mut public = false;
+    if words.peek() == Some(&"pub") {
+        words.next();
+        public = true;
+        if words.peek().is_some_and(|w| w.ends_with(')')) {
+            words.next();
+            public = false;
+        }
+    }
+
+    while let Some(word) = words.next() {
+        let word = word.trim_matches('"');
+        if QUALIFIERS.contains(&word) || word == "C" {
+            continue;
+        }
+
+        // "const" is either a qualifier ("const fn") or an item of its own.
+        if word == "const"
+            && words
+                .peek()
+                .is_some_and(|w| *w == "fn" || QUALIFIERS.contains(w))
+        {
+            continue;
+        }
+
+        if !KINDS.contains(&word) {
+            return None;
+        }
+
+        let name = if word == "impl" {
+            words
+                .take_while(|w| *w != "{" && *w != "where")
+                .filter(|w| !w.contains(['>', '\'', ',']))
+                .collect::<Vec<_>>()
+                .join(" ")
+        } else {
+            words
+                .next()
+                .unwrap_or("")
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect()
+        };
+        let kind = word.trim_end_matches('!').to_string();
+        return Some(Item { kind, name, public });
+    }
+
+    None
+}
+
+impl<'a> Iterator for CommentIterator<'a> {
+    type Item = RawComment;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        /// parser state
+        #[allow(clippy::enum_variant_names)]
+        enum FileState {
+            /// not currently inside a comment
+            NoComment,
+            /// currently inside a line comment (with the starting line number
+            /// and the indentation of the first line)
+            InLineComment(usize, usize),
+            /// currently inside a block comment, nested this deep
+            InBlockComment(usize, usize),
+        }
+
+        // Precondition: we are not currently in a comment.
+        let mut state = FileState::NoComment;
+
+        // Keep track of the lines in the current comment.
+        let mut lines = Vec::new();
+
+        // Read lines until we run out of lines in the file or return early.
+        while let Some(&raw_line) = self.lines.get(self.next) {
+            let line_numz = self.next;
+            let mut line = raw_line.trim_start().trim_end();
+            let indent = indentation(raw_line);
+
+            match state {
+                FileState::NoComment => {
+                    // If a block comment ended partway through this line,
+                    // look at what follows it.  A line comment there stands
+                    // alone, like one after code.
+                    let mut resumed = self.resume.take();
+                    if let Some(open) = self.open_string.take() {
+                        // This line starts inside a string literal, so
+                        // nothing here is a comment until it ends (if it
+                        // does).
+                        match string_end(raw_line, 0, &open) {
+                            Some(end) => resumed = Some(end),
+                            None => {
+                                self.open_string = Some(open);
+                                self.next += 1;
+                                continue;
+                            }
+                        }
+                    }
+                    if let Some((start, depth)) = self.open_block.take() {
+                        // This line starts inside a block comment that began
+                        // after some code.  We don't report those, so skip to
+                        // where it ends (if it does).
+                        match block_comment_depth(depth, raw_line, &self.syntax)
+                        {
+                            (0, end) => resumed = Some(end),
+                            (depth, _) => {
+                                self.open_block = Some((start, depth));
+                                self.next += 1;
+                                continue;
+                            }
+                        }
+                    }
+                    if let Some(offset) = resumed {
+                        line = raw_line[offset..].trim();
+                    }
+
+                    let syntax = self.syntax;
+                    let block_start = syntax
+                        .block_comment
+                        .is_some_and(|(open, _)| line.starts_with(open));
+                    if line.starts_with(syntax.line_comment)
+                        && resumed.is_none()
+                    {
+                        // We've found the start of a line comment.
+                        lines.push(self.kept(raw_line, line, None));
+                        state = FileState::InLineComment(line_numz + 1, indent);
+                    } else if block_start {
+                        // We've found the start of a block comment.
+                        let line = self.kept(raw_line, line, None);
+                        lines.push(line);
+                        match block_comment_depth(0, line, &syntax) {
+                            (0, end) => {
+                                // It ends on the same line.
+                                return Some(self.emit_block(
+                                    line_numz + 1,
+                                    &lines,
+                                    end,
+                                ));
+                            }
+                            (depth, _) => {
+                                state = FileState::InBlockComment(
+                                    line_numz + 1,
+                                    depth,
+                                );
+                            }
+                        }
+                    } else {
+                        let (end, stray) = trailing_comment(line, &syntax);
+                        if stray {
+                            self.warnings.push(ParseWarning {
+                                line: line_numz + 1,
+                                message: String::from(
+                                    "\"*/\" outside of a block comment",
+                                ),
+                            });
+                        }
+                        match end {
+                            CodeEnd::Comment(i) => {
+                                // We've found a line comment after some code.
+                                // It stands alone: it's about the code it
+                                // follows, not part of any comment on the
+                                // lines after it.
+                                self.track_scope(raw_line);
+                                self.next += 1;
+                                let text =
+                                    self.kept(raw_line, &line[i..], None);
+                                let mut raw = self.emit(line_numz + 1, &[text]);
+                                raw.attached = true;
+                                return Some(raw);
+                            }
+                            CodeEnd::InString(open) => {
+                                // The line ends inside a string literal.
+                                // Whatever's in it until it ends isn't a
+                                // comment, even if it looks like one.
+                                self.track_scope(raw_line);
+                                self.open_string = Some(open);
+                            }
+                            CodeEnd::InBlockComment(depth) => {
+                                // The line ends inside a block comment.  As
+                                // with a string literal, skip what's in it.
+                                self.track_scope(raw_line);
+                                self.open_block = Some((line_numz + 1, depth));
+                            }
+                            CodeEnd::Code => {
+                                // We haven't found a comment yet.  Note
+                                // whether this line starts an item, skip it,
+                                // and continue the loop.
+                                self.track_scope(raw_line);
+                            }
+                        }
+                    }
+                }
+
+                FileState::InLineComment(start, start_indent) => {
+                    let dedented =
+                        self.split_on_dedent && indent < start_indent;
+                    if !line.starts_with(self.syntax.line_comment) || dedented {
+                        // We got to the end of a line comment.  Emit it.  (We
+                        // leave this line to be looked at again next time,
+                        // since it might start another comment.)
+                        return Some(self.emit(start, &lines));
+                    } else {
+                        // We're still in a line comment.  Keep reading.
+                        let first = (start, lines[0]);
+                        lines.push(self.kept(raw_line, line, Some(first)));
+                    }
+                }
+
+                FileState::InBlockComment(start, depth) => {
+                    // The end of the comment is found on the line as kept, so
+                    // that it's where `emit_block()` looks for it.
+                    let line =
+                        self.kept(raw_line, line, Some((start, lines[0])));
+                    lines.push(line);
+                    match block_comment_depth(depth, line, &self.syntax) {
+                        (0, end) => {
+                            // We got to the end of the outermost block
+                            // comment.  Emit it.
+                            return Some(self.emit_block(start, &lines, end));
+                        }
+                        (depth, _) => {
+                            state = FileState::InBlockComment(start, depth);
+                        }
+                    }
+                }
+            }
+
+            self.next += 1;
+        }
+
+        match state {
+            FileState::NoComment => {
+                // We got to the end of the file without finding any more
+                // comments.  We ought not to have accumulated any lines.
+                assert_eq!(lines.len(), 0);
+                if let Some((start, _)) = self.open_block.take() {
+                    self.warnings.push(ParseWarning {
+                        line: start,
+                        message: String::from(
+                            "file ended inside a block comment",
+                        ),
+                    });
+                }
+                None
+            }
+
+            FileState::InLineComment(start, _) => {
+                // A line comment is complete at the end of the file, so
+                // there's nothing to warn about.
+                Some(self.emit(start, &lines))
+            }
+
+            FileState::InBlockComment(start, _) => {
+                self.warnings.push(ParseWarning {
+                    line: start,
+                    message: String::from("file ended inside a block comment"),
+                });
+                Some(self.emit(start, &lines))
+            }
+        }
+    }
+}
+
+/// macros whose invocations mark unfinished code
+pub const DEFERRED_WORK_MACROS: &[&str] =
+    &["todo", "unimplemented", "unreachable"];
+
+/// An invocation of one of the [`DEFERRED_WORK_MACROS`] found in code
+#[derive(Clone, Debug)]
+pub struct MacroCall {
+    /// line number (starting from 1) where the invocation starts
+    pub line: usize,
+    /// column (starting from 1, in characters) where the invocation starts
+    pub column: usize,
+    /// name of the macro, including the "!" (e.g., "todo!")
+    pub name: String,
+    /// source text of the whole invocation (e.g., `todo!("parse flags")`)
+    pub text: String,
+    /// the message given to the macro, if its first argument is a string
+    /// literal (escapes are left as they appear in the source)
+    pub message: Option<String>,
+}
+
+/// Returns the invocations of [`DEFERRED_WORK_MACROS`] in Rust source `input`
+///
+/// Unlike [`CommentIterator`], this has to look at code, so it does just
+/// enough lexing to skip over comments, string literals, and character
+/// literals (whose contents might look like macro invocations).
+pub fn find_macro_calls(input: &str) -> Vec<MacroCall> {
+    let bytes = input.as_bytes();
+    let mut calls = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b == b'\n' {
+            line += 1;
+            i += 1;
+        } else if bytes[i..].starts_with(b"//") {
+            i = input[i..].find('\n').map_or(bytes.len(), |n| i + n);
+        } else if bytes[i..].starts_with(b"/*") {
+            let end = skip_block_comment(input, i);
+            line += input[i..end].matches('\n').count();
+            i = end;
+        } else if b == b'"' {
+            let end = skip_string(input, i);
+            line += input[i..end].matches('\n').count();
+            i = end;
+        } else if b == b'\'' {
+            i = skip_char_or_lifetime(input, i);
+        } else if b.is_ascii_alphabetic() || b == b'_' {
+            let start = i;
+            while i < bytes.len()
+                && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_')
+            {
+                i += 1;
+            }
+            let ident = &input[start..i];
+
+            // Raw strings (r"..." and r#"..."#, possibly with a "b" prefix)
+            if (ident == "r" || ident == "br")
+                && matches!(bytes.get(i), Some(b'"' | b'#'))
+            {
+                let end = skip_raw_string(input, i);
+                line += input[i..end].matches('\n').count();
+                i = end;
+                continue;
+            }
+
+            if !DEFERRED_WORK_MACROS.contains(&ident) {
+                continue;
+            }
+            let after_bang = match input[i..].trim_start().strip_prefix('!') {
+                Some(rest) => input.len() - rest.len(),
+                None => continue,
+            };
+            let open = input.len() - input[after_bang..].trim_start().len();
+            if !matches!(bytes.get(open), Some(b'(' | b'[' | b'{')) {
+                continue;
+            }
+
+            let end = skip_delimited(input, open);
+            let args = input[open + 1..end.saturating_sub(1).max(open + 1)]
+                .trim_start();
+            let message = args.starts_with('"').then(|| {
+                let literal_end = skip_string(args, 0);
+                args[1..literal_end.saturating_sub(1).max(1)].to_string()
+            });
+            let line_start = input[..start].rfind('\n').map_or(0, |n| n + 1);
+            calls.push(MacroCall {
+                line,
+                column: input[line_start..start].chars().count() + 1,
+                name: format!("{}!", ident),
+                text: input[start..end].to_string(),
+                message,
+            });
+            line += input[start..end].matches('\n').count();
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+
+    calls
+}
+
+/// Returns the index just past the (possibly nested) block comment starting
+/// at `start`
+fn skip_block_comment(input: &str, start: usize) -> usize {
+    let bytes = input.as_bytes();
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"/*") {
+            depth += 1;
+            i += 2;
+        } else if bytes[i..].starts_with(b"*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    input.len()
+}
+
+/// Returns how deeply nested in block comments we are after `line`, given
+/// that we were `depth` deep before it, and where in `line` that is
+///
+/// Block comments nest in Rust, so each "/*" goes one level deeper and each
+/// "*/" comes back out one.  In languages where they don't nest, the depth is
+/// only ever 0 or 1.  If the outermost comment ends on this line, the index
+/// returned is just past its "*/"; otherwise, it's the end of the line.
+fn block_comment_depth(
+    mut depth: usize,
+    line: &str,
+    syntax: &Syntax,
+) -> (usize, usize) {
+    let Some((open, close)) = syntax.block_comment else {
+        return (0, line.len());
+    };
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(open.as_bytes())
+            && (syntax.nested_blocks || depth == 0)
+        {
+            depth += 1;
+            i += open.len();
+        } else if bytes[i..].starts_with(close.as_bytes()) {
+            depth = depth.saturating_sub(1);
+            i += close.len();
+            if depth == 0 {
+                break;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    (depth, i)
+}
+
+/// How a line of code ends, as found by [`trailing_comment()`]
+enum CodeEnd {
+    /// with a line comment starting at this index
+    Comment(usize),
+    /// inside a string literal that continues onto the next line
+    InString(OpenString),
+    /// inside a block comment that continues onto the next line, nested
+    /// this deep
+    InBlockComment(usize),
+    /// some other way
+    Code,
+}
+
+/// A string literal that didn't end on the line where it started
+struct OpenString {
+    /// what ends it (e.g., `"` or `"##`)
+    terminator: String,
+    /// whether a backslash escapes the next character (i.e., it's not a raw
+    /// string)
+    escapes: bool,
+}
+
+/// Returns the index just past the end of the string literal `open` in
+/// `input`, looking from `start`, if it ends there
+fn string_end(input: &str, start: usize, open: &OpenString) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let mut i = start;
+    while i < bytes.len() {
+        if open.escapes && bytes[i] == b'\\' {
+            i += 2;
+        } else if bytes[i..].starts_with(open.terminator.as_bytes()) {
+            return Some(i + open.terminator.len());
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Returns whether there's a comment after the code on `line` (and if so,
+/// the index of its `//`), or whether the line ends inside a string literal
+/// or block comment, along with whether there's a "*/" in the code that
+/// doesn't end a block comment
+///
+/// This skips over string and character literals (and block comments) on
+/// the line, so that the "//" in `"http://"` or `'/'` doesn't count.  The
+/// caller keeps track of strings and block comments that continue from one
+/// line to the next.  Only in Rust do we know enough about the code to tell
+/// a "*/" that's out of place, or that a block comment continues.
+fn trailing_comment(line: &str, syntax: &Syntax) -> (CodeEnd, bool) {
+    if !syntax.rust {
+        let end = trailing_comment_other(line, syntax)
+            .map_or(CodeEnd::Code, CodeEnd::Comment);
+        return (end, false);
+    }
+    let bytes = line.as_bytes();
+    let mut stray = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if bytes[i..].starts_with(b"//") {
+            return (CodeEnd::Comment(i), stray);
+        } else if bytes[i..].starts_with(b"/*") {
+            match block_comment_depth(0, &line[i..], syntax) {
+                (0, end) => i += end,
+                (depth, _) => return (CodeEnd::InBlockComment(depth), stray),
+            }
+        } else if bytes[i..].starts_with(b"*/") {
+            stray = true;
+            i += 2;
+        } else if b == b'"' {
+            let open =
+                OpenString { terminator: String::from("\""), escapes: true };
+            match string_end(line, i + 1, &open) {
+                Some(end) => i = end,
+                None => return (CodeEnd::InString(open), stray),
+            }
+        } else if b == b'\'' {
+            i = skip_char_or_lifetime(line, i);
+        } else if b.is_ascii_alphabetic() || b == b'_' {
+            let start = i;
+            while i < bytes.len()
+                && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_')
+            {
+                i += 1;
+            }
+            let ident = &line[start..i];
+            if (ident == "r" || ident == "br" || ident == "cr")
+                && matches!(bytes.get(i), Some(b'"' | b'#'))
+            {
+                let hashes =
+                    line[i..].bytes().take_while(|b| *b == b'#').count();
+                let body = i + hashes;
+                if bytes.get(body) != Some(&b'"') {
+                    i = body;
+                    continue;
+                }
+                let open = OpenString {
+                    terminator: format!("\"{}", "#".repeat(hashes)),
+                    escapes: false,
+                };
+                match string_end(line, body + 1, &open) {
+                    Some(end) => i = end,
+                    None => return (CodeEnd::InString(open), stray),
+                }
+            }
+        } else {
+            i += 1;
+        }
+    }
+    (CodeEnd::Code, stray)
+}
+
+/// Like [`trailing_comment()`], for a language other than Rust
+///
+/// All we know how to skip is block comments and strings quoted with `"` or
+/// `'` (with backslash escapes).  A "#" only starts a comment after
+/// whitespace, since in shell, something like `$#` or `a#b` doesn't.
+fn trailing_comment_other(line: &str, syntax: &Syntax) -> Option<usize> {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        if rest.starts_with(syntax.line_comment.as_bytes())
+            && (syntax.line_comment != "#"
+                || i == 0
+                || bytes[i - 1].is_ascii_whitespace())
+        {
+            return Some(i);
+        } else if let Some((open, close)) = syntax
+            .block_comment
+            .filter(|(open, _)| rest.starts_with(open.as_bytes()))
+        {
+            i = line[i + open.len()..]
+                .find(close)
+                .map_or(bytes.len(), |n| i + open.len() + n + close.len());
+        } else if bytes[i] == b'"' || bytes[i] == b'\'' {
+            i = skip_quoted(line, i);
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Returns the index just past the string literal whose opening quote is at
+/// `start`
+fn skip_string(input: &str, start: usize) -> usize {
+    skip_quoted(input, start)
+}
+
+/// Returns the index just past the text quoted by the character at `start`
+/// (and ending with the same character), allowing backslash escapes
+fn skip_quoted(input: &str, start: usize) -> usize {
+    let bytes = input.as_bytes();
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b if b == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    input.len()
+}
+
+/// Returns the index just past the raw string literal whose `#`s or opening
+/// quote start at `start`
+fn skip_raw_string(input: &str, start: usize) -> usize {
+    let hashes = input[start..].bytes().take_while(|b| *b == b'#').count();
+    let body = start + hashes;
+    if input.as_bytes().get(body) != Some(&b'"') {
+        return body;
+    }
+    let terminator = format!("\"{}", "#".repeat(hashes));
+    input[body + 1..]
+        .find(&terminator)
+        .map_or(input.len(), |n| body + 1 + n + terminator.len())
+}
+
+/// Returns the index just past the character literal or lifetime whose quote
+/// is at `start`
+fn skip_char_or_lifetime(input: &str, start: usize) -> usize {
+    let bytes = input.as_bytes();
+    let Some(c) = input[start + 1..].chars().next() else {
+        return input.len();
+    };
+
+    if c == '\\' {
+        // an escaped character, like '\n' or '\''
+        bytes
+            .get(start + 3..)
+            .unwrap_or(&[])
+            .iter()
+            .position(|b| *b == b'\'')
+            .map_or(input.len(), |n| start + 3 + n + 1)
+    } else if bytes.get(start + 1 + c.len_utf8()) == Some(&b'\'') {
+        // a simple character, like 'a'
+        start + 1 + c.len_utf8() + 1
+    } else {
+        // a lifetime, like 'a
+        start + 1
+    }
+}
+
+/// Returns the index just past the closing delimiter matching the opening
+/// one at `start`, skipping over string and character literals
+fn skip_delimited(input: &str, start: usize) -> usize {
+    let bytes = input.as_bytes();
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'(' | b'[' | b'{' => {
+                depth += 1;
+                i += 1;
+            }
+            b')' | b']' | b'}' => {
+                depth -= 1;
+                i += 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            b'"' => i = skip_string(input, i),
+            b'\'' => i = skip_char_or_lifetime(input, i),
+            _ => i += 1,
+        }
+    }
+    input.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the first and last lines and the text of each comment that
+    /// [`CommentIterator`] finds in the Rust source `src`
+    fn comments(src: &str) -> Vec<(usize, usize, String)> {
+        CommentIterator::new(src)
+            .map(|c| (c.line, c.end_line, c.contents))
+            .collect()
+    }
+
+    fn comment(
+        line: usize,
+        end_line: usize,
+        text: &str,
+    ) -> (usize, usize, String) {
+        (line, end_line, text.to_string())
+    }
+
+    #[test]
+    fn single_line_block_comments() {
+        let src = "\
+/* TODO fix */
+fn f() {}
+    /* XXX indented */   
+/**/
+// after
+";
+        assert_eq!(
+            comments(src),
+            [
+                comment(1, 1, "/* TODO fix */\n"),
+                comment(3, 3, "/* XXX indented */\n"),
+                comment(4, 4, "/**/\n"),
+                comment(5, 5, "// after\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn block_comments_ending_before_code() {
+        let src = "\
+/* TODO fix */ let x = 1;
+/* FIXME: this
+   one ends */ let y = 2; // XXX trailing
+let z = 3;
+// after
+";
+        let found: Vec<RawComment> = CommentIterator::new(src).collect();
+        let summary: Vec<_> = found
+            .iter()
+            .map(|c| (c.line, c.end_line, c.contents.as_str(), c.attached))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (1, 1, "/* TODO fix */\n", true),
+                (2, 3, "/* FIXME: this\none ends */\n", true),
+                (3, 3, "// XXX trailing\n", true),
+                (5, 5, "// after\n", false),
+            ]
+        );
+        // The trailing comment's column is where it is on its line.
+        assert_eq!(found[2].indents, [26]);
+    }
+
+    #[test]
+    fn string_literals_are_not_comments() {
+        let src = r##"
+let msg = "TODO: not really // a todo";
+let url = "http://example.com"; // TODO real
+let q = '"'; // XXX after a quote character
+let s = "first
+// TODO inside a string
+/* FIXME also inside */
+still \" inside"; // TODO after the string
+let r = r#"raw "string"
+// TODO inside a raw string
+"#;
+// after
+"##;
+        assert_eq!(
+            comments(src),
+            [
+                comment(3, 3, "// TODO real\n"),
+                comment(4, 4, "// XXX after a quote character\n"),
+                comment(8, 8, "// TODO after the string\n"),
+                comment(12, 12, "// after\n"),
+            ]
+        );
+    }
+
+    /// a file that has a bit of everything, with LF line endings
+    const LF_SOURCE: &str = "\
+// TODO: a line comment
+//   continued
+fn f() {} // XXX trailing
+/*
+ * FIXME a block
+ */
+/* one line */ let x = \"a
+string\";
+*/
+// TODO on the last line";
+
+    #[test]
+    fn crlf_is_like_lf() {
+        let expected = comments(LF_SOURCE);
+        assert_eq!(expected.len(), 5);
+        assert_eq!(expected[4], comment(10, 10, "// TODO on the last line\n"));
+
+        let crlf = LF_SOURCE.replace('\n', "\r\n");
+        assert_eq!(comments(&crlf), expected);
+        assert_eq!(comments(&format!("{}\r\n", crlf)), expected);
+        assert_eq!(comments(&format!("{}\r", crlf)), expected);
+        assert_eq!(comments(&format!("{}\n", LF_SOURCE)), expected);
+
+        // Warnings (here, about the stray "*/") are the same too.
+        let warnings = |src: &str| {
+            let mut iter = CommentIterator::new(src);
+            iter.by_ref().for_each(drop);
+            iter.warnings()
+                .iter()
+                .map(|w| (w.line, w.message.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(warnings(&crlf), warnings(LF_SOURCE));
+        assert_eq!(warnings(LF_SOURCE).len(), 1);
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        let src = "\
+/* outer
+   /* inner TODO */
+   still outer
+    /* two /* levels */ deep */
+*/
+fn f() {}
+// after
+";
+        assert_eq!(
+            comments(src),
+            [
+                comment(
+                    1,
+                    5,
+                    "/* outer\n/* inner TODO */\nstill outer\n\
+                     /* two /* levels */ deep */\n*/\n"
+                ),
+                comment(7, 7, "// after\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_block_comment_tokens_on_one_line() {
+        let src = "/* a /* b */ c */ fn f() {} // TODO after\n";
+        assert_eq!(
+            comments(src),
+            [
+                comment(1, 1, "/* a /* b */ c */\n"),
+                comment(1, 1, "// TODO after\n")
+            ]
+        );
+    }
+
+    #[test]
+    fn c_block_comments_do_not_nest() {
+        let src = "/* a /* b */ int x; // TODO after\n";
+        let found: Vec<_> = CommentIterator::new(src)
+            .syntax(Syntax::C)
+            .map(|c| c.contents)
+            .collect();
+        assert_eq!(found, ["/* a /* b */\n", "// TODO after\n"]);
+    }
+}
@@ -0,0 +1,144 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Encoding reports as protobuf messages
+//!
+//! The schema is in `proto/todos.proto`.  It's small enough that we encode it
+//! by hand here rather than generating code for it.
+
+use todos::Comment;
+use todos::CommentTracker;
+use todos::ReportHeader;
+
+/// wire type for varints
+const VARINT: u64 = 0;
+/// wire type for length-delimited fields (strings and embedded messages)
+const LEN: u64 = 2;
+
+/// Returns the `Report` message for the comments in `tracker` (and `header`,
+/// if given), preceded by its length
+pub fn encode_report_delimited(
+    tracker: &CommentTracker,
+    header: Option<&ReportHeader>,
+) -> Vec<u8> {
+    let report = encode_report(tracker, header);
+    let mut buf = Vec::new();
+    put_varint(&mut buf, report.len() as u64);
+    buf.extend(report);
+    buf
+}
+
+/// Returns the `Report` message for the comments in `tracker` (and `header`,
+/// if given)
+pub fn encode_report(
+    tracker: &CommentTracker,
+    header: Option<&ReportHeader>,
+) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for comments in tracker.comments_by_kind.values() {
+        let mut comments: Vec<_> = comments.iter().collect();
+        comments.sort_by(|a, b| {
+            (&a.file, a.position()).cmp(&(&b.file, b.position()))
+        });
+        for c in comments {
+            put_message(&mut buf, 1, &encode_comment(c));
+        }
+    }
+    for label in tracker.comments_by_kind.keys() {
+        let mut count = Vec::new();
+        put_string(&mut count, 1, label);
+        put_uint(&mut count, 2, tracker.count(label) as u64);
+        put_message(&mut buf, 2, &count);
+    }
+    put_uint(&mut buf, 3, tracker.total() as u64);
+    if let Some(header) = header {
+        put_message(&mut buf, 4, &encode_header(header));
+    }
+    buf
+}
+
+fn encode_header(header: &ReportHeader) -> Vec<u8> {
+    let mut buf = Vec::new();
+    put_string(&mut buf, 1, &header.version);
+    put_strings(&mut buf, 2, &header.roots);
+    put_strings(&mut buf, 3, &header.keywords);
+    for (glob, keywords) in &header.scopes {
+        let mut scope = Vec::new();
+        put_string(&mut scope, 1, glob);
+        put_strings(&mut scope, 2, keywords);
+        put_message(&mut buf, 4, &scope);
+    }
+    put_strings(&mut buf, 5, &header.exclude);
+    buf
+}
+
+fn encode_comment(c: &Comment) -> Vec<u8> {
+    let mut buf = Vec::new();
+    put_string(&mut buf, 1, &c.label);
+    let (line, column) = c.position();
+    put_string(&mut buf, 2, &c.file);
+    put_uint(&mut buf, 3, line as u64);
+    put_string(&mut buf, 4, &c.contents);
+    // These are "optional" fields, so they're written whenever they're
+    // present, even if they have the default value.
+    if let Some(tag) = &c.tag {
+        put_key(&mut buf, 5, LEN);
+        put_bytes(&mut buf, tag.as_bytes());
+    }
+    if let Some(issue) = c.issue {
+        put_key(&mut buf, 6, VARINT);
+        put_varint(&mut buf, issue);
+    }
+    put_string(&mut buf, 7, &c.id);
+    put_key(&mut buf, 8, VARINT);
+    put_varint(&mut buf, column as u64);
+    buf
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_key(buf: &mut Vec<u8>, field: u64, wire_type: u64) {
+    put_varint(buf, (field << 3) | wire_type);
+}
+
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Writes a proto3 `uint64` field, which is left out if it's zero
+fn put_uint(buf: &mut Vec<u8>, field: u64, value: u64) {
+    if value != 0 {
+        put_key(buf, field, VARINT);
+        put_varint(buf, value);
+    }
+}
+
+/// Writes a proto3 `string` field, which is left out if it's empty
+fn put_string(buf: &mut Vec<u8>, field: u64, value: &str) {
+    if !value.is_empty() {
+        put_key(buf, field, LEN);
+        put_bytes(buf, value.as_bytes());
+    }
+}
+
+/// Writes a `repeated string` field, whose elements are written even if
+/// they're empty
+fn put_strings(buf: &mut Vec<u8>, field: u64, values: &[String]) {
+    for value in values {
+        put_key(buf, field, LEN);
+        put_bytes(buf, value.as_bytes());
+    }
+}
+
+fn put_message(buf: &mut Vec<u8>, field: u64, message: &[u8]) {
+    put_key(buf, field, LEN);
+    put_bytes(buf, message);
+}
@@ -0,0 +1,873 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Small backtracking regular-expression matcher
+//!
+//! This supports the subset of the usual (Perl/`regex`-crate-style) syntax that
+//! people tend to use in command-line filters:
+//!
+//! * literals, with `\` escapes for metacharacters, plus `\n`, `\r`, `\t`
+//! * `.` (any character except newline, or any character at all under `(?s)`)
+//! * character classes (`[abc]`, `[^a-z0-9_]`) and the shorthand classes `\d`,
+//!   `\w`, `\s` and their negations `\D`, `\W`, `\S`
+//! * anchors `^` and `$` (line boundaries under `(?m)`) and word boundaries
+//!   `\b` and `\B`
+//! * groups: capturing `(...)`, named `(?P<name>...)` or `(?<name>...)`, and
+//!   non-capturing `(?:...)`
+//! * alternation `|`
+//! * quantifiers `*`, `+`, `?`, `{n}`, `{n,}`, `{n,m}`, each optionally
+//!   followed by `?` to make it lazy
+//! * flags `i` (case-insensitive), `m` (multi-line) and `s` (dot matches
+//!   newline), given as `(?ims)` at the very start of the pattern
+//!
+//! The pattern is compiled to a small program that's run by backtracking over
+//! the characters of the input.  The matcher remembers which steps of the
+//! program it has already tried at which positions and never tries one twice,
+//! so the time it takes is at most proportional to the size of the program
+//! times the length of the input, even for patterns like `(a*)*b` that take
+//! exponential time in a naive backtracking engine.  Counted repetitions
+//! (`{n,m}`) are compiled by copying what they repeat, and patterns whose
+//! programs would be too large are rejected.
+
+use anyhow::anyhow;
+use anyhow::bail;
+use std::ops::Range;
+
+/// A compiled regular expression
+#[derive(Clone, Debug)]
+pub struct Regex {
+    /// compiled form of the pattern
+    program: Vec<Inst>,
+    /// number of capture groups, including the implicit group 0
+    ngroups: usize,
+    /// names of the named capture groups, with their indexes
+    names: Vec<(String, usize)>,
+    flags: Flags,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Flags {
+    /// `i`: match case-insensitively
+    ignore_case: bool,
+    /// `m`: `^` and `$` match at line boundaries
+    multi_line: bool,
+    /// `s`: `.` matches newline, too
+    dot_all: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    /// matches the empty string
+    Empty,
+    Literal(char),
+    /// `.`
+    Any,
+    Class(Class),
+    /// `^`
+    Start,
+    /// `$`
+    End,
+    /// `\b` (true) or `\B` (false)
+    WordBoundary(bool),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    /// a group, with its capture index if it's a capturing group
+    Group(Box<Node>, Option<usize>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+}
+
+/// A set of characters, like `[a-z_]` or `\d`
+#[derive(Clone, Debug)]
+struct Class {
+    negated: bool,
+    /// inclusive ranges of characters
+    ranges: Vec<(char, char)>,
+}
+
+/// Capture positions (in characters), indexed by group number
+type Captures = Vec<Option<(usize, usize)>>;
+
+/// One step of a compiled pattern
+///
+/// A pattern compiles to a list of these, run from the first.  Unless it says
+/// otherwise, each one goes on to the next in the list if it succeeds.
+#[derive(Clone, Debug)]
+enum Inst {
+    /// consumes one character matching `Node::Literal`, `Node::Any` or
+    /// `Node::Class`
+    Char(Node),
+    /// checks `Node::Start`, `Node::End` or `Node::WordBoundary`
+    Assert(Node),
+    /// goes on at the first instruction or, if that doesn't lead to a match,
+    /// the second
+    Split(usize, usize),
+    Jump(usize),
+    /// records the current position in a capture slot: group `n` starts at
+    /// slot `2 * n` and ends at slot `2 * n + 1`
+    Save(usize),
+    /// the whole pattern has matched
+    Match,
+}
+
+/// The most instructions a compiled pattern may have
+///
+/// Counted repetitions are compiled by copying what they repeat, so without a
+/// limit a short pattern like `(a{1000}){1000}` would take a very long time
+/// (and a lot of memory) to compile.
+const MAX_PROGRAM_LEN: usize = 10_000;
+
+/// Work left for `Regex::run`
+enum Job {
+    /// try instruction `.0` at position `.1`
+    Try(usize, usize),
+    /// put back the old value of a capture slot when backtracking
+    Restore(usize, Option<usize>),
+}
+
+/// The set of (instruction, position) pairs that a search has tried
+struct Visited {
+    bits: Vec<u64>,
+    npositions: usize,
+}
+
+impl Visited {
+    fn new(ninsts: usize, npositions: usize) -> Visited {
+        Visited {
+            bits: vec![0; (ninsts * npositions).div_ceil(64)],
+            npositions,
+        }
+    }
+
+    /// Records that instruction `pc` has been tried at position `pos`,
+    /// returning whether it hadn't been before
+    fn insert(&mut self, pc: usize, pos: usize) -> bool {
+        let i = pc * self.npositions + pos;
+        let (word, bit) = (i / 64, 1 << (i % 64));
+        let new = self.bits[word] & bit == 0;
+        self.bits[word] |= bit;
+        new
+    }
+}
+
+impl Regex {
+    /// Compiles `pattern`, returning an error describing what's wrong with it
+    /// if it's not valid
+    pub fn new(pattern: &str) -> Result<Regex, anyhow::Error> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            ngroups: 1,
+            names: Vec::new(),
+            depth: 0,
+        };
+        let flags = parser.parse_leading_flags()?;
+        let root = parser.parse_alternation()?;
+        if parser.pos < parser.chars.len() {
+            // The only way for the top-level parse to stop early is on an
+            // unbalanced ")".
+            bail!(
+                "invalid regex {:?}: unmatched \")\" at offset {}",
+                pattern,
+                parser.pos
+            );
+        }
+
+        let mut program = Vec::new();
+        compile(&root, &mut program)
+            .and_then(|()| push(&mut program, Inst::Match))
+            .map_err(|()| {
+                anyhow!(
+                    "invalid regex {:?}: more than {} steps once repetitions \
+                     are expanded",
+                    pattern,
+                    MAX_PROGRAM_LEN
+                )
+            })?;
+
+        Ok(Regex {
+            program,
+            ngroups: parser.ngroups,
+            names: parser.names,
+            flags,
+        })
+    }
+
+    /// Returns the index of the capture group called `name`, if there is one
+    pub fn group_index(&self, name: &str) -> Option<usize> {
+        self.names.iter().find(|(n, _)| n == name).map(|(_, index)| *index)
+    }
+
+    /// Returns each non-overlapping match of this regex in `text`, leftmost
+    /// first, as the byte range that each capture group matched (indexed by
+    /// group number, so the whole match comes first), or `None` for groups
+    /// that didn't take part in the match
+    pub fn captures_all(&self, text: &str) -> Vec<Vec<Option<Range<usize>>>> {
+        let chars: Vec<char> = text.chars().collect();
+        let offsets: Vec<usize> = text
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while start <= chars.len() {
+            let Some(caps) = self.search(&chars, start..=chars.len()) else {
+                break;
+            };
+            let (begin, end) = caps[0].expect("whole match is captured");
+            matches.push(
+                caps.iter()
+                    .map(|c| c.map(|(s, e)| offsets[s]..offsets[e]))
+                    .collect(),
+            );
+            // Go on after the match, or (for an empty match) after the next
+            // character, so that we don't find the same empty match forever.
+            start = if end > begin { end } else { end + 1 };
+        }
+        matches
+    }
+
+    /// Returns whether this regex matches anywhere in `text`
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        self.search(&chars, 0..=chars.len()).is_some()
+    }
+
+    /// Runs the program from each of the positions in `starts` in turn,
+    /// returning the captures (as character positions) of the first match
+    ///
+    /// A failed attempt from one starting position would fail the same way
+    /// from any other, because nothing the program does after it starts
+    /// depends on where it started, so `visited` is shared between them.
+    fn search(
+        &self,
+        chars: &[char],
+        starts: impl IntoIterator<Item = usize>,
+    ) -> Option<Captures> {
+        let mut visited = Visited::new(self.program.len(), chars.len() + 1);
+        let mut slots = vec![None; 2 * self.ngroups];
+        starts.into_iter().find_map(|start| {
+            let end = self.run(chars, start, &mut visited, &mut slots)?;
+            let mut caps: Captures = (0..self.ngroups)
+                .map(|i| slots[2 * i].zip(slots[2 * i + 1]))
+                .collect();
+            caps[0] = Some((start, end));
+            Some(caps)
+        })
+    }
+
+    /// Runs the program from position `start`, returning where the match
+    /// ends, if there is one
+    ///
+    /// This is a backtracking search, preferring the first branch of each
+    /// `Split`, so it finds the same match a recursive backtracker would.  It
+    /// keeps its own stack rather than recursing, and it never tries an
+    /// instruction at a position it has already tried it at: if it got there
+    /// before and we're still looking, it didn't lead to a match then
+    /// either.  That bounds the work by the size of the program times the
+    /// length of the text.
+    fn run(
+        &self,
+        chars: &[char],
+        start: usize,
+        visited: &mut Visited,
+        slots: &mut [Option<usize>],
+    ) -> Option<usize> {
+        let mut stack = vec![Job::Try(0, start)];
+        while let Some(job) = stack.pop() {
+            let (mut pc, mut pos) = match job {
+                Job::Try(pc, pos) => (pc, pos),
+                Job::Restore(slot, old) => {
+                    slots[slot] = old;
+                    continue;
+                }
+            };
+            while visited.insert(pc, pos) {
+                match &self.program[pc] {
+                    Inst::Char(node) => match chars.get(pos) {
+                        Some(c) if self.matches_char(node, *c) => {
+                            pc += 1;
+                            pos += 1;
+                        }
+                        _ => break,
+                    },
+                    Inst::Assert(node) => {
+                        if !self.holds(node, chars, pos) {
+                            break;
+                        }
+                        pc += 1;
+                    }
+                    Inst::Split(first, second) => {
+                        stack.push(Job::Try(*second, pos));
+                        pc = *first;
+                    }
+                    Inst::Jump(target) => pc = *target,
+                    Inst::Save(slot) => {
+                        stack.push(Job::Restore(*slot, slots[*slot]));
+                        slots[*slot] = Some(pos);
+                        pc += 1;
+                    }
+                    Inst::Match => return Some(pos),
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns whether zero-width assertion `node` holds at position `pos`
+    fn holds(&self, node: &Node, chars: &[char], pos: usize) -> bool {
+        match node {
+            Node::Start => {
+                pos == 0 || (self.flags.multi_line && chars[pos - 1] == '\n')
+            }
+            Node::End => {
+                pos == chars.len()
+                    || (self.flags.multi_line && chars[pos] == '\n')
+            }
+            Node::WordBoundary(want) => {
+                let before = pos > 0 && is_word_char(chars[pos - 1]);
+                let after = pos < chars.len() && is_word_char(chars[pos]);
+                (before != after) == *want
+            }
+            _ => unreachable!("not an assertion"),
+        }
+    }
+
+    /// Returns whether single-character node `node` matches `c`
+    fn matches_char(&self, node: &Node, c: char) -> bool {
+        match node {
+            Node::Literal(l) => {
+                *l == c
+                    || (self.flags.ignore_case && chars_eq_ignore_case(*l, c))
+            }
+            Node::Any => self.flags.dot_all || c != '\n',
+            Node::Class(class) => {
+                let contains = |c: char| {
+                    class.ranges.iter().any(|(lo, hi)| *lo <= c && c <= *hi)
+                };
+                let found = contains(c)
+                    || (self.flags.ignore_case
+                        && (c.to_lowercase().any(contains)
+                            || c.to_uppercase().any(contains)));
+                found != class.negated
+            }
+            _ => unreachable!("not a single-character node"),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn chars_eq_ignore_case(a: char, b: char) -> bool {
+    a.to_lowercase().eq(b.to_lowercase())
+}
+
+/// Recursive-descent parser for the pattern syntax described in the module
+/// documentation
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    /// number of capture groups seen so far (including group 0)
+    ngroups: usize,
+    /// names of the named capture groups seen so far, with their indexes
+    names: Vec<(String, usize)>,
+    /// number of groups we're inside of
+    depth: usize,
+}
+
+/// The deepest that groups may be nested
+///
+/// The parser (and the compiler after it) recurses once per level of nesting,
+/// so without a limit a pattern with enough parentheses would overflow the
+/// stack.
+const MAX_NESTING: usize = 250;
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!(
+            "invalid regex {:?}: {} at offset {}",
+            self.chars.iter().collect::<String>(),
+            message,
+            self.pos
+        )
+    }
+
+    fn parse_leading_flags(&mut self) -> Result<Flags, anyhow::Error> {
+        let mut flags = Flags::default();
+        let rest = &self.chars[self.pos..];
+        if rest.len() < 3 || rest[0] != '(' || rest[1] != '?' {
+            return Ok(flags);
+        }
+
+        // Only "(?flags)" sets flags.  "(?:", "(?P<", etc. are groups.
+        let Some(close) = rest.iter().position(|c| *c == ')') else {
+            return Ok(flags);
+        };
+        let letters = &rest[2..close];
+        if letters.is_empty() || !letters.iter().all(|c| c.is_alphabetic()) {
+            return Ok(flags);
+        }
+
+        for c in letters {
+            match c {
+                'i' => flags.ignore_case = true,
+                'm' => flags.multi_line = true,
+                's' => flags.dot_all = true,
+                _ => {
+                    return Err(self.error(&format!("unsupported flag {:?}", c)))
+                }
+            }
+        }
+        self.pos += close + 1;
+        Ok(flags)
+    }
+
+    fn parse_alternation(&mut self) -> Result<Node, anyhow::Error> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.eat('|') {
+            branches.push(self.parse_concat()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Node::Alternate(branches)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, anyhow::Error> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_quantifier(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().unwrap(),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn parse_quantifier(&mut self, atom: Node) -> Result<Node, anyhow::Error> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                self.pos += 1;
+                let min = self.parse_number()?;
+                let max = if self.eat(',') {
+                    if self.peek() == Some('}') {
+                        None
+                    } else {
+                        Some(self.parse_number()?)
+                    }
+                } else {
+                    Some(min)
+                };
+                if self.peek() != Some('}') {
+                    return Err(self.error("unterminated repetition"));
+                }
+                if max.is_some_and(|max| max < min) {
+                    return Err(self.error("invalid repetition range"));
+                }
+                (min, max)
+            }
+            _ => return Ok(atom),
+        };
+        self.pos += 1;
+
+        if matches!(
+            atom,
+            Node::Empty | Node::Start | Node::End | Node::WordBoundary(_)
+        ) {
+            return Err(self.error("nothing to repeat"));
+        }
+
+        let greedy = !self.eat('?');
+        if matches!(self.peek(), Some('*' | '+' | '?' | '{')) {
+            return Err(self.error("repeated repetition"));
+        }
+        Ok(Node::Repeat { node: Box::new(atom), min, max, greedy })
+    }
+
+    fn parse_number(&mut self) -> Result<usize, anyhow::Error> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .map_err(|_| self.error("expected a number in repetition"))
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, anyhow::Error> {
+        let c = self.peek().unwrap();
+        self.pos += 1;
+        match c {
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '(' => self.parse_group(),
+            '[' => self.parse_class(),
+            '\\' => self.parse_escape(false).map(|e| match e {
+                Escape::Char(c) => Node::Literal(c),
+                Escape::Class(class) => Node::Class(class),
+                Escape::WordBoundary(b) => Node::WordBoundary(b),
+            }),
+            '*' | '+' | '?' => {
+                self.pos -= 1;
+                Err(self.error("nothing to repeat"))
+            }
+            c => Ok(Node::Literal(c)),
+        }
+    }
+
+    fn parse_group(&mut self) -> Result<Node, anyhow::Error> {
+        let index = if self.eat('?') {
+            if self.eat(':') {
+                None
+            } else if self.eat('<') || (self.eat('P') && self.eat('<')) {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c != '>') {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                if !self.eat('>') {
+                    return Err(self.error("unterminated group name"));
+                }
+                if name.is_empty()
+                    || !name.chars().all(|c| c.is_alphanumeric() || c == '_')
+                {
+                    return Err(self.error("invalid group name"));
+                }
+                if self.names.iter().any(|(n, _)| *n == name) {
+                    return Err(self.error("duplicate group name"));
+                }
+                let index = self.next_group();
+                self.names.push((name, index));
+                Some(index)
+            } else {
+                return Err(self.error("unsupported group syntax"));
+            }
+        } else {
+            Some(self.next_group())
+        };
+
+        if self.depth == MAX_NESTING {
+            return Err(self.error(&format!(
+                "groups nested more than {} deep",
+                MAX_NESTING
+            )));
+        }
+        self.depth += 1;
+        let inner = self.parse_alternation()?;
+        self.depth -= 1;
+        if !self.eat(')') {
+            return Err(self.error("unclosed group"));
+        }
+        Ok(Node::Group(Box::new(inner), index))
+    }
+
+    fn next_group(&mut self) -> usize {
+        self.ngroups += 1;
+        self.ngroups - 1
+    }
+
+    fn parse_class(&mut self) -> Result<Node, anyhow::Error> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.peek() {
+                None => return Err(self.error("unclosed character class")),
+                Some(']') if !first => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(c) => c,
+            };
+            first = false;
+            self.pos += 1;
+
+            let lo = if c == '\\' {
+                match self.parse_escape(true)? {
+                    Escape::Char(c) => c,
+                    Escape::Class(class) => {
+                        // Negated shorthand classes inside a bracketed class
+                        // (e.g., "[\D]") would need set subtraction.
+                        if class.negated {
+                            return Err(self.error(
+                                "negated shorthand class inside brackets",
+                            ));
+                        }
+                        ranges.extend(class.ranges);
+                        continue;
+                    }
+                    Escape::WordBoundary(_) => unreachable!(),
+                }
+            } else {
+                c
+            };
+
+            if self.peek() == Some('-')
+                && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']')
+            {
+                self.pos += 1;
+                let hi = match self.peek().unwrap() {
+                    '\\' => {
+                        self.pos += 1;
+                        match self.parse_escape(true)? {
+                            Escape::Char(c) => c,
+                            _ => return Err(self.error("invalid class range")),
+                        }
+                    }
+                    c => {
+                        self.pos += 1;
+                        c
+                    }
+                };
+                if hi < lo {
+                    return Err(self.error("invalid class range"));
+                }
+                ranges.push((lo, hi));
+            } else {
+                ranges.push((lo, lo));
+            }
+        }
+
+        Ok(Node::Class(Class { negated, ranges }))
+    }
+
+    /// Parses the part of an escape sequence after the `\`
+    fn parse_escape(
+        &mut self,
+        in_class: bool,
+    ) -> Result<Escape, anyhow::Error> {
+        let Some(c) = self.peek() else {
+            return Err(self.error("trailing backslash"));
+        };
+        self.pos += 1;
+        let class = |negated, ranges: &[(char, char)]| {
+            Ok(Escape::Class(Class { negated, ranges: ranges.to_vec() }))
+        };
+        const DIGIT: &[(char, char)] = &[('0', '9')];
+        const WORD: &[(char, char)] =
+            &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+        const SPACE: &[(char, char)] = &[
+            ('\t', '\r'),
+            (' ', ' '),
+            ('\u{85}', '\u{85}'),
+            ('\u{a0}', '\u{a0}'),
+        ];
+        match c {
+            'd' => class(false, DIGIT),
+            'D' => class(true, DIGIT),
+            'w' => class(false, WORD),
+            'W' => class(true, WORD),
+            's' => class(false, SPACE),
+            'S' => class(true, SPACE),
+            'b' if !in_class => Ok(Escape::WordBoundary(true)),
+            'B' if !in_class => Ok(Escape::WordBoundary(false)),
+            'n' => Ok(Escape::Char('\n')),
+            'r' => Ok(Escape::Char('\r')),
+            't' => Ok(Escape::Char('\t')),
+            c if c.is_alphanumeric() => {
+                self.pos -= 1;
+                Err(self.error(&format!("unsupported escape \\{}", c)))
+            }
+            c => Ok(Escape::Char(c)),
+        }
+    }
+}
+
+/// Appends the instructions for `node` to `program`, failing if that would
+/// make it too long
+fn compile(node: &Node, program: &mut Vec<Inst>) -> Result<(), ()> {
+    match node {
+        Node::Empty => Ok(()),
+        Node::Literal(_) | Node::Any | Node::Class(_) => {
+            push(program, Inst::Char(node.clone()))
+        }
+        Node::Start | Node::End | Node::WordBoundary(_) => {
+            push(program, Inst::Assert(node.clone()))
+        }
+        Node::Concat(nodes) => {
+            nodes.iter().try_for_each(|node| compile(node, program))
+        }
+        Node::Alternate(branches) => {
+            // Each branch but the last is tried by a "Split" before it, and
+            // jumps to the end when it's done.
+            let (last, rest) = branches.split_last().expect("has branches");
+            let mut jumps = Vec::new();
+            for branch in rest {
+                let split = program.len();
+                push(program, Inst::Split(split + 1, 0))?;
+                compile(branch, program)?;
+                jumps.push(program.len());
+                push(program, Inst::Jump(0))?;
+                program[split] = Inst::Split(split + 1, program.len());
+            }
+            compile(last, program)?;
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+            Ok(())
+        }
+        Node::Group(inner, None) => compile(inner, program),
+        Node::Group(inner, Some(index)) => {
+            push(program, Inst::Save(2 * index))?;
+            compile(inner, program)?;
+            push(program, Inst::Save(2 * index + 1))
+        }
+        Node::Repeat { node, min, max, greedy } => {
+            for _ in 0..*min {
+                compile(node, program)?;
+            }
+            let split = |take: usize, skip: usize| {
+                if *greedy {
+                    Inst::Split(take, skip)
+                } else {
+                    Inst::Split(skip, take)
+                }
+            };
+            match max {
+                None => {
+                    // Loop back to the "Split" after each repetition.  One
+                    // that matches the empty string comes back to it at the
+                    // same position, which has already been tried, so it
+                    // can't loop forever.
+                    let start = program.len();
+                    push(program, Inst::Split(0, 0))?;
+                    compile(node, program)?;
+                    push(program, Inst::Jump(start))?;
+                    program[start] = split(start + 1, program.len());
+                }
+                Some(max) => {
+                    // Each optional repetition is tried by a "Split" that
+                    // skips to the end if it's not taken.
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        push(program, Inst::Split(0, 0))?;
+                        compile(node, program)?;
+                    }
+                    let end = program.len();
+                    for s in splits {
+                        program[s] = split(s + 1, end);
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Appends `inst` to `program`, failing if that would make it too long
+fn push(program: &mut Vec<Inst>, inst: Inst) -> Result<(), ()> {
+    if program.len() >= MAX_PROGRAM_LEN {
+        return Err(());
+    }
+    program.push(inst);
+    Ok(())
+}
+
+enum Escape {
+    Char(char),
+    Class(Class),
+    WordBoundary(bool),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the text matched by each capture group of the first match of
+    /// `pattern` in `text`
+    fn captures(pattern: &str, text: &str) -> Vec<Option<String>> {
+        let caps = Regex::new(pattern).unwrap().captures_all(text);
+        caps[0].iter().map(|c| c.clone().map(|r| text[r].to_string())).collect()
+    }
+
+    fn some(text: &str) -> Option<String> {
+        Some(text.to_string())
+    }
+
+    #[test]
+    fn backtracks_like_perl() {
+        assert_eq!(
+            captures("(a+)(a*)", "aaa"),
+            [some("aaa"), some("aaa"), some("")]
+        );
+        assert_eq!(
+            captures("(a+?)(a*)", "aaa"),
+            [some("aaa"), some("a"), some("aa")]
+        );
+        assert_eq!(
+            captures("(a|ab)(c|bcd)", "abcd"),
+            [some("abcd"), some("a"), some("bcd")]
+        );
+        assert_eq!(captures("x(a|b)*y", "xabay"), [some("xabay"), some("a")]);
+        assert_eq!(captures("(a)|b", "b"), [some("b"), None]);
+        assert_eq!(captures("a{2,3}?", "aaaa"), [some("aa")]);
+        assert_eq!(captures("(a*)*b", "aab"), [some("aab"), some("aa")]);
+    }
+
+    #[test]
+    fn nested_repetition_is_not_exponential() {
+        let re = Regex::new("(a*)*b").unwrap();
+        assert!(!re.is_match(&"a".repeat(32)));
+        assert!(re.is_match(&format!("{}b", "a".repeat(32))));
+        assert!(Regex::new("(a|aa)+$").unwrap().is_match(&"a".repeat(1000)));
+    }
+
+    #[test]
+    fn optional_runs_are_not_exponential() {
+        let pattern = format!("{}{}", "a?".repeat(30), "a".repeat(30));
+        let re = Regex::new(&pattern).unwrap();
+        assert!(re.is_match(&"a".repeat(32)));
+        assert!(!re.is_match(&"a".repeat(29)));
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let nested = |n| format!("{}a{}", "(".repeat(n), ")".repeat(n));
+        assert!(Regex::new(&nested(250)).unwrap().is_match("a"));
+        let error = Regex::new(&nested(20000)).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("groups nested more than 250 deep at offset 251"));
+    }
+
+    #[test]
+    fn huge_repetitions_are_rejected() {
+        let error = Regex::new("(a{1000}){1000}").unwrap_err();
+        assert!(error.to_string().contains("more than 10000 steps"));
+    }
+}
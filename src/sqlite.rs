@@ -0,0 +1,119 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Writing comments to a SQLite database
+//!
+//! The database has one table, `comments`, with a row per comment (per
+//! label, as in every report).  Rather than linking SQLite, we generate a SQL
+//! script and run it with the `sqlite3` command-line tool.
+
+use anyhow::bail;
+use anyhow::Context;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+use todos::CommentStyle;
+use todos::CommentTracker;
+
+/// Creates the `comments` table and its indexes, unless they already exist
+const SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS comments (
+    id TEXT NOT NULL,
+    label TEXT NOT NULL,
+    file TEXT NOT NULL,
+    line INTEGER NOT NULL,
+    column INTEGER NOT NULL,
+    style TEXT NOT NULL,
+    text TEXT NOT NULL,
+    tag TEXT,
+    issue INTEGER,
+    item_kind TEXT,
+    item_name TEXT,
+    enclosing TEXT,
+    attached INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS comments_label ON comments (label);
+CREATE INDEX IF NOT EXISTS comments_file ON comments (file);
+";
+
+/// Writes the comments in `tracker` to the `comments` table of the SQLite
+/// database at `path`, creating the database and table as needed
+///
+/// Rows from any previous run are replaced, all in one transaction.
+pub fn write_database(
+    path: &Path,
+    tracker: &CommentTracker,
+) -> Result<(), anyhow::Error> {
+    let script = script(tracker);
+    // A relative path starting with "-" would be taken as an option.  (Older
+    // versions of sqlite3 don't understand "--".)
+    let path = if path.is_relative() {
+        Path::new(".").join(path)
+    } else {
+        path.to_path_buf()
+    };
+    let mut child = Command::new("sqlite3")
+        .arg("-bail")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("running sqlite3")?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(script.as_bytes()).context("writing to sqlite3")?;
+    drop(stdin);
+
+    let output = child.wait_with_output().context("running sqlite3")?;
+    if !output.status.success() {
+        bail!(
+            "writing {:?}: sqlite3 failed: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Returns the SQL script that [`write_database()`] runs
+pub fn script(tracker: &CommentTracker) -> String {
+    let mut script = String::from(SCHEMA);
+    script.push_str("BEGIN;\nDELETE FROM comments;\n");
+    for c in tracker.comments_by_kind.values().flatten() {
+        let (line, column) = c.position();
+        let style = match c.style {
+            CommentStyle::Comment(_) => "comment",
+            CommentStyle::Macro { .. } => "macro",
+            CommentStyle::Markdown => "markdown",
+        };
+        writeln!(
+            script,
+            "INSERT INTO comments VALUES \
+             ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {});",
+            quote(&c.id),
+            quote(&c.label),
+            quote(&c.file),
+            line,
+            column,
+            quote(style),
+            quote(&c.contents),
+            c.tag.as_deref().map_or(String::from("NULL"), quote),
+            c.issue.map_or(String::from("NULL"), |n| n.to_string()),
+            c.item.as_ref().map_or(String::from("NULL"), |i| quote(&i.kind)),
+            c.item.as_ref().map_or(String::from("NULL"), |i| quote(&i.name)),
+            c.enclosing.as_deref().map_or(String::from("NULL"), quote),
+            u8::from(c.attached),
+        )
+        .unwrap();
+    }
+    script.push_str("COMMIT;\n");
+    script
+}
+
+/// Returns `s` as a SQL string literal
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
@@ -0,0 +1,94 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Tags assigned to particular comments out-of-band
+//!
+//! A tag file has one mapping per line, like:
+//!
+//! ```text
+//! src/lib.rs:42 P1
+//! src/parser.rs:7 owner=alice
+//! ```
+//!
+//! The path is relative to the root of the tree being scanned, and the line is
+//! the line where the comment starts.  The tag is everything after the first
+//! run of whitespace.  Blank lines and lines starting with `#` are ignored.
+
+use anyhow::anyhow;
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::path::Path;
+use todos::CommentTracker;
+
+/// Maps comment locations to tags
+#[derive(Debug, Default)]
+pub struct TagMap {
+    tags: BTreeMap<(String, usize), String>,
+}
+
+impl TagMap {
+    /// Loads the tag file at `path`
+    pub fn load(path: &Path) -> Result<TagMap, anyhow::Error> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("read {:?}", path.display()))?;
+        TagMap::parse(&contents)
+            .with_context(|| format!("parse {:?}", path.display()))
+    }
+
+    /// Parses the contents of a tag file
+    pub fn parse(contents: &str) -> Result<TagMap, anyhow::Error> {
+        let mut tags = BTreeMap::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error =
+                || anyhow!("line {}: expected \"FILE:LINE TAG\"", i + 1);
+            let (location, tag) =
+                line.split_once(char::is_whitespace).ok_or_else(error)?;
+            let (file, line_number) =
+                location.rsplit_once(':').ok_or_else(error)?;
+            let line_number = line_number.parse().map_err(|_| error())?;
+            tags.insert(
+                (normalize(file).to_string(), line_number),
+                tag.trim().to_string(),
+            );
+        }
+
+        Ok(TagMap { tags })
+    }
+
+    /// Returns the tag for the comment starting on line `line` of `file`
+    /// (relative to the root of the tree), if any
+    pub fn get(&self, file: &Path, line: usize) -> Option<&str> {
+        let file = file.to_string_lossy();
+        self.tags.get(&(normalize(&file).to_string(), line)).map(String::as_str)
+    }
+
+    /// Attaches tags to the matching comments in `tracker`, whose file names
+    /// start with one of `roots`
+    pub fn apply(&self, roots: &[&Path], tracker: &mut CommentTracker) {
+        for c in tracker.comments_by_kind.values_mut().flatten() {
+            let file = Path::new(&c.file);
+            let file = roots
+                .iter()
+                .find_map(|root| file.strip_prefix(root).ok())
+                .unwrap_or(file);
+            if let Some(tag) = self.get(file, c.line) {
+                c.tag = Some(tag.to_string());
+            }
+        }
+    }
+}
+
+/// Strips any leading "./" from a path so that "./src/lib.rs" and
+/// "src/lib.rs" are treated alike
+fn normalize(mut file: &str) -> &str {
+    while let Some(rest) = file.strip_prefix("./") {
+        file = rest;
+    }
+    file
+}
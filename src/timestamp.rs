@@ -0,0 +1,185 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Parsing points in time and durations given on the command line
+
+use anyhow::anyhow;
+use anyhow::bail;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// Parses `input` as either an RFC 3339 timestamp (e.g.,
+/// "2023-01-31T12:00:00Z") or a duration (e.g., "36h"), which is taken to mean
+/// that long before `now`
+pub fn parse_since(
+    input: &str,
+    now: SystemTime,
+) -> Result<SystemTime, anyhow::Error> {
+    if input.contains('-') {
+        return parse_rfc3339(input);
+    }
+
+    let duration = parse_duration(input)?;
+    now.checked_sub(duration)
+        .ok_or_else(|| anyhow!("duration {:?} is too long", input))
+}
+
+/// Parses a duration like "90s", "15m", "36h", "7d", "2w", or "1d12h"
+pub fn parse_duration(input: &str) -> Result<Duration, anyhow::Error> {
+    let mut seconds: u64 = 0;
+    let mut rest = input.trim();
+    if rest.is_empty() {
+        bail!("invalid duration {:?}", input);
+    }
+
+    while !rest.is_empty() {
+        let ndigits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        let (digits, after) = rest.split_at(ndigits);
+        let unit = after.chars().next();
+        let multiplier = match unit {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 60 * 60,
+            Some('d') => 24 * 60 * 60,
+            Some('w') => 7 * 24 * 60 * 60,
+            _ => bail!(
+                "invalid duration {:?}: expected a number followed by one \
+                 of s, m, h, d, or w",
+                input
+            ),
+        };
+        let count: u64 = digits
+            .parse()
+            .map_err(|_| anyhow!("invalid duration {:?}", input))?;
+        seconds = count
+            .checked_mul(multiplier)
+            .and_then(|s| s.checked_add(seconds))
+            .ok_or_else(|| anyhow!("duration {:?} is too long", input))?;
+        rest = &after[1..];
+    }
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Parses an RFC 3339 timestamp like "2023-01-31T12:00:00Z" or
+/// "2023-01-31T04:00:00.5-08:00"
+///
+/// As a convenience, a bare date ("2023-01-31") means midnight UTC.
+pub fn parse_rfc3339(input: &str) -> Result<SystemTime, anyhow::Error> {
+    let error = || {
+        anyhow!(
+            "invalid timestamp {:?}: expected RFC 3339 (e.g., \
+             \"2023-01-31T12:00:00Z\")",
+            input
+        )
+    };
+    let number = |s: &str| -> Result<i64, anyhow::Error> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(error());
+        }
+        s.parse().map_err(|_| error())
+    };
+
+    let (date, time) = match input.split_once(['T', 't', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (input, None),
+    };
+
+    let mut date_parts = date.splitn(3, '-');
+    let year = number(date_parts.next().ok_or_else(error)?)?;
+    let month = number(date_parts.next().ok_or_else(error)?)?;
+    let day = number(date_parts.next().ok_or_else(error)?)?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(error());
+    }
+
+    let mut seconds = days_from_civil(year, month, day) * 24 * 60 * 60;
+    let mut nanos = 0;
+    if let Some(time) = time {
+        // Split off the UTC offset.
+        let (clock, offset) = if let Some(clock) = time.strip_suffix(['Z', 'z'])
+        {
+            (clock, 0)
+        } else if let Some(i) = time.rfind(['+', '-']) {
+            let (clock, offset) = time.split_at(i);
+            let (hours, minutes) =
+                offset[1..].split_once(':').ok_or_else(error)?;
+            let offset = number(hours)? * 60 * 60 + number(minutes)? * 60;
+            (clock, if time.as_bytes()[i] == b'-' { -offset } else { offset })
+        } else {
+            return Err(error());
+        };
+
+        // Split off fractional seconds.
+        let (clock, fraction) = match clock.split_once('.') {
+            Some((clock, fraction)) => (clock, Some(fraction)),
+            None => (clock, None),
+        };
+        let mut clock_parts = clock.splitn(3, ':');
+        let hour = number(clock_parts.next().ok_or_else(error)?)?;
+        let minute = number(clock_parts.next().ok_or_else(error)?)?;
+        let second = number(clock_parts.next().ok_or_else(error)?)?;
+        if hour > 23 || minute > 59 || second > 60 {
+            return Err(error());
+        }
+        if let Some(fraction) = fraction {
+            number(fraction)?;
+            let digits: String =
+                fraction.chars().chain("000000000".chars()).take(9).collect();
+            nanos = digits.parse().map_err(|_| error())?;
+        }
+
+        seconds += hour * 60 * 60 + minute * 60 + second - offset;
+    }
+
+    let seconds = u64::try_from(seconds)
+        .map_err(|_| anyhow!("timestamp {:?} is before 1970", input))?;
+    Ok(SystemTime::UNIX_EPOCH + Duration::new(seconds, nanos))
+}
+
+/// Returns the year (in UTC) that `time` falls in
+pub fn year_of(time: SystemTime) -> i64 {
+    let seconds = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(error) => -(error.duration().as_secs() as i64),
+    };
+    civil_from_days(seconds.div_euclid(24 * 60 * 60)).0
+}
+
+/// Returns the date (year, month, day) that is `days` days after 1970-01-01
+/// in the proleptic Gregorian calendar
+///
+/// This is Howard Hinnant's `civil_from_days` algorithm, the inverse of
+/// [`days_from_civil()`].
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+        - day_of_era / 146096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month =
+        if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+/// Returns the number of days between 1970-01-01 and the given date in the
+/// proleptic Gregorian calendar
+///
+/// This is Howard Hinnant's `days_from_civil` algorithm.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era =
+        year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}